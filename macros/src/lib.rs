@@ -1,5 +1,6 @@
 use proc_macro::TokenStream;
 use quote::quote;
+use syn::ext::IdentExt;
 use syn::{self, Expr, GenericParam, parse_quote};
 use syn::{DataEnum, DataUnion, DeriveInput};
 
@@ -9,6 +10,69 @@ enum DeriveType {
     To,
 }
 
+// Every named field is a column named after it unless told otherwise. On the
+// struct only `null` and `write_null` are allowed, they apply to every
+// `Option` field that does not set its own.
+#[derive(Default)]
+struct FieldAttrs {
+    field: Option<Expr>,
+    flatten: bool,
+    skip_serializing: bool,
+    skip_deserializing: bool,
+    null: Vec<Expr>,
+    write_null: Option<Expr>,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[syn::Attribute], container: bool) -> syn::Result<Self> {
+        let mut ret = FieldAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("csv")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("null") {
+                    ret.null.push(meta.value()?.parse()?);
+                } else if meta.path.is_ident("write_null") {
+                    ret.write_null = Some(meta.value()?.parse()?);
+                } else if container {
+                    return Err(meta.error("unsupported csv attribute on a struct"));
+                } else if meta.path.is_ident("field") {
+                    ret.field = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("flatten") {
+                    ret.flatten = true;
+                } else if meta.path.is_ident("skip") {
+                    ret.skip_serializing = true;
+                    ret.skip_deserializing = true;
+                } else if meta.path.is_ident("skip_serializing") {
+                    ret.skip_serializing = true;
+                } else if meta.path.is_ident("skip_deserializing") {
+                    ret.skip_deserializing = true;
+                } else {
+                    return Err(meta.error("unsupported csv attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(ret)
+    }
+}
+
+// `T` of an `Option<T>` field.
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if path.qself.is_some() || segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(syn::GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
+
 #[proc_macro_derive(CSVHeader, attributes(csv))]
 pub fn csv_header_derive(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).unwrap();
@@ -40,7 +104,7 @@ fn impl_csv_derive(ast: &DeriveInput, dt: DeriveType) -> TokenStream {
     let mut generics = generics.clone();
     for param in &mut generics.params {
         if let GenericParam::Type(type_param) = param {
-            type_param.bounds.push(parse_quote!(Display))
+            type_param.bounds.push(parse_quote!(::std::fmt::Display))
         }
     }
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
@@ -64,63 +128,108 @@ fn impl_csv_derive(ast: &DeriveInput, dt: DeriveType) -> TokenStream {
         }
     };
 
+    let container = match FieldAttrs::parse(&ast.attrs, true) {
+        Ok(attrs) => attrs,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
     match data {
         syn::Data::Struct(s) => {
             for field in s.fields.iter() {
-                if field.ident.is_none() {
-                } else {
-                    let ident = field.ident.as_ref().unwrap();
-                    for attr in &field.attrs {
-                        if attr.path().is_ident("csv") {
-                            match attr.parse_args() {
-                                Err(_) => {}
-                                Ok(attr) => match attr {
-                                    Expr::Assign(expr) => {
-                                        if let Expr::Path(path) = *expr.left {
-                                            if path.path.is_ident("field") {
-                                                let right = expr.right;
-                                                match dt{
-                                                    DeriveType::Header=>fn_body.extend(quote! {
-                                                        inner.push(#right.to_string());
-                                                    }),
-                                                    DeriveType::From=>fn_body.extend(quote! {
-                                                        match m.get(#right) {
-                                                            Some(v) => {
-                                                                inner.#ident = v.parse()?;
-                                                            },
-                                                            None => {return Err(ErrorKind::ErrMissField(#right.to_string()).into());},
-                                                        }
-                                                    }),
-                                                    DeriveType::To=>fn_body.extend(quote! {
-                                                        inner.push(self.#ident.to_string());
-                                                    })
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Expr::Path(expr) => {
-                                        if expr.path.is_ident("flatten") {
-                                            let typ = field.ty.clone();
-                                            match dt {
-                                                DeriveType::Header => fn_body.extend(quote! {
-                                                    let tmp = #typ::get_header();
-                                                    inner.extend(tmp);
-                                                }),
-                                                DeriveType::From => fn_body.extend(quote! {
-                                                    inner.#ident = #typ::from_csv(header, record)?;
-                                                }),
-                                                DeriveType::To => fn_body.extend(quote! {
-                                                    let tmp = self.#ident.to_csv();
-                                                    inner.extend(tmp);
-                                                }),
-                                            }
-                                        }
-                                    }
-                                    _ => {}
+                let Some(ident) = field.ident.as_ref() else {
+                    continue;
+                };
+                let attrs = match FieldAttrs::parse(&field.attrs, false) {
+                    Ok(attrs) => attrs,
+                    Err(err) => return err.to_compile_error().into(),
+                };
+                let skip = match dt {
+                    DeriveType::Header | DeriveType::To => attrs.skip_serializing,
+                    DeriveType::From => attrs.skip_deserializing,
+                };
+                if skip {
+                    continue;
+                }
+                let ty = &field.ty;
+                if attrs.flatten {
+                    match dt {
+                        DeriveType::Header => fn_body.extend(quote! {
+                            inner.extend(<#ty as ::csv::HeaderCSV>::get_header());
+                        }),
+                        DeriveType::From => fn_body.extend(quote! {
+                            inner.#ident = <#ty as ::csv::FromCSV>::from_csv(header, record)?;
+                        }),
+                        DeriveType::To => fn_body.extend(quote! {
+                            inner.extend(::csv::ToCSV::to_csv(&self.#ident));
+                        }),
+                    }
+                    continue;
+                }
+                let name = match attrs.field {
+                    Some(expr) => quote!(#expr),
+                    None => {
+                        let name = ident.unraw().to_string();
+                        quote!(#name)
+                    }
+                };
+                // a field left out of `get_header` has no column in a file
+                // read without a header row, it keeps its default then
+                let missing = match attrs.skip_serializing {
+                    true => quote!({}),
+                    false => quote!({return Err(::csv::Error::ErrMissField(#name.to_string()));}),
+                };
+                if let Some(inner_ty) = option_inner(ty) {
+                    let null = match attrs.null.is_empty() {
+                        true => &container.null,
+                        false => &attrs.null,
+                    };
+                    let write_null = match attrs.write_null.or(container.write_null.clone()) {
+                        Some(expr) => quote!(#expr),
+                        None => quote!(""),
+                    };
+                    match dt {
+                        DeriveType::Header => fn_body.extend(quote! {
+                            inner.push(#name.to_string());
+                        }),
+                        DeriveType::From => fn_body.extend(quote! {
+                            match m.get(#name) {
+                                Some(v) if v.is_empty() #(|| v.as_str() == #null)* => {
+                                    inner.#ident = None;
+                                },
+                                Some(v) => {
+                                    inner.#ident = Some(v.parse().map_err(|e| {
+                                        ::csv::Error::parse(#name, v, ::std::any::type_name::<#inner_ty>(), e)
+                                    })?);
                                 },
+                                None => #missing,
                             }
-                        }
+                        }),
+                        DeriveType::To => fn_body.extend(quote! {
+                            inner.push(match &self.#ident {
+                                Some(v) => v.to_string(),
+                                None => #write_null.to_string(),
+                            });
+                        }),
                     }
+                    continue;
+                }
+                match dt {
+                    DeriveType::Header => fn_body.extend(quote! {
+                        inner.push(#name.to_string());
+                    }),
+                    DeriveType::From => fn_body.extend(quote! {
+                        match m.get(#name) {
+                            Some(v) => {
+                                inner.#ident = v.parse().map_err(|e| {
+                                    ::csv::Error::parse(#name, v, ::std::any::type_name::<#ty>(), e)
+                                })?;
+                            },
+                            None => #missing,
+                        }
+                    }),
+                    DeriveType::To => fn_body.extend(quote! {
+                        inner.push(self.#ident.to_string());
+                    }),
                 }
             }
         }
@@ -147,7 +256,7 @@ fn impl_csv_derive(ast: &DeriveInput, dt: DeriveType) -> TokenStream {
         },
         DeriveType::From => quote! {
             impl #impl_generics ::csv::FromCSV for #ident #ty_generics #where_clause{
-                fn from_csv(header: &Vec<String>, record: &Vec<String>) -> Result<Self>{
+                fn from_csv(header: &[String], record: &[String]) -> ::csv::Result<Self>{
                     #fn_body
                     Ok(inner)
                 }
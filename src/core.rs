@@ -0,0 +1,873 @@
+use crate::Terminator;
+use crate::dialect::Dialect;
+use crate::err::*;
+use crate::reader::Trim;
+use crate::record::{ByteRecord, Position, trim_bytes};
+use crate::writer::QuoteStyle;
+use memchr::{memchr, memchr_iter, memchr2, memchr2_iter, memmem};
+
+const QUOTE_LEN: usize = 1;
+const ESCAPE_LEN: usize = 1;
+
+pub(crate) enum Feed {
+    Eof,
+    // empty or comment line
+    Skip,
+    // the record goes on in a quoted field on the next line
+    More,
+    Record,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parsed {
+    /// A record was read into the output.
+    Record,
+    /// The buffered input holds no complete record, feed more of it.
+    NeedInput,
+    /// `finish` was called and every record has been read.
+    End,
+}
+
+/// An I/O free parser, bytes are pushed in arbitrary chunks with `feed` and
+/// records pulled with `read_record`. Fields are not decoded, the dialect's
+/// encoding is ignored.
+///
+/// It does no I/O but still allocates and uses `std`, the crate does not
+/// support `no_std`.
+pub struct Parser {
+    parser: LineParser,
+    trim: Trim,
+    header: bool,
+    buf: Vec<u8>,
+    // start of the unread input in `buf` and how far it has been searched
+    // for a terminator
+    start: usize,
+    scan: usize,
+    finished: bool,
+    // dropping the rest of a record over the byte limit, up to the next
+    // terminator
+    discard: bool,
+    line: Vec<u8>,
+    // the record being parsed, it may span several `read_record` calls
+    record: ByteRecord,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser {
+            parser: LineParser::new(),
+            trim: Trim::None,
+            header: false,
+            buf: Vec::new(),
+            start: 0,
+            scan: 0,
+            finished: false,
+            discard: false,
+            line: Vec::new(),
+            record: ByteRecord::new(),
+        }
+    }
+
+    pub fn with_dialect(mut self, dialect: Dialect) -> Result<Self> {
+        dialect.validate()?;
+        let p = &mut self.parser;
+        p.comma = dialect.delimiter;
+        p.quote = dialect.quote;
+        p.escape = dialect.escape;
+        p.double_quote = dialect.double_quote;
+        p.terminator = dialect.terminator;
+        p.comment = dialect.comment;
+        self.trim = dialect.trim;
+        self.header = dialect.header;
+        Ok(self)
+    }
+
+    pub fn with_allow_diff_field_num(mut self, allow_diff_field_num: bool) -> Self {
+        self.parser.allow_diff_field_num = allow_diff_field_num;
+        self
+    }
+
+    pub fn with_lazy_quote(mut self, lazy_quote: bool) -> Self {
+        self.parser.lazy_quote = lazy_quote;
+        self
+    }
+
+    pub fn with_trim_quoted(mut self, trim_quoted: bool) -> Self {
+        self.parser.trim_quoted = trim_quoted;
+        self
+    }
+
+    pub fn with_max_record_bytes(mut self, max: usize) -> Self {
+        self.parser.max_record_bytes = Some(max);
+        self
+    }
+
+    pub fn with_max_fields(mut self, max: usize) -> Self {
+        self.parser.max_fields = Some(max);
+        self
+    }
+
+    pub fn with_max_field_bytes(mut self, max: usize) -> Self {
+        self.parser.max_field_bytes = Some(max);
+        self
+    }
+
+    /// Appends the next chunk of input, it may end anywhere in a record.
+    pub fn feed(&mut self, mut chunk: &[u8]) {
+        if self.discard {
+            let term = self.parser.term();
+            let Some(i) = memchr(term, chunk) else {
+                self.parser.skip(chunk.len(), false);
+                return;
+            };
+            self.parser.skip(i + 1, true);
+            self.discard = false;
+            chunk = &chunk[i + 1..];
+        }
+        if self.start > 0 && self.start == self.buf.len() {
+            self.buf.clear();
+            self.scan = 0;
+            self.start = 0;
+        } else if self.start >= self.buf.len() / 2 {
+            self.buf.drain(..self.start);
+            self.scan -= self.start;
+            self.start = 0;
+        }
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Marks the end of the input, the last line may lack its terminator.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Bytes fed but not parsed yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    /// Position of the next record in the whole input.
+    pub fn position(&self) -> Position {
+        Position::new(
+            self.parser.offset,
+            self.parser.num_line + 1,
+            self.parser.num_record,
+        )
+    }
+
+    /// Parses the next record into `record`. On error the bad record is
+    /// dropped and the next call goes on with the following line.
+    pub fn read_record(&mut self, record: &mut ByteRecord) -> Result<Parsed> {
+        let term = self.parser.term();
+        loop {
+            let end = match memchr(term, &self.buf[self.scan..]) {
+                Some(i) => self.scan + i + 1,
+                None if self.finished => self.buf.len(),
+                None => {
+                    let len = self.buf.len() - self.start;
+                    if let Err(err) = self.parser.check_record_bytes(len, &self.record) {
+                        // the rest of the record is dropped by `feed`
+                        self.parser.skip(len, false);
+                        self.buf.truncate(self.start);
+                        self.scan = self.start;
+                        self.discard = true;
+                        return Err(err);
+                    }
+                    self.scan = self.buf.len();
+                    return Ok(Parsed::NeedInput);
+                }
+            };
+            self.line.clear();
+            self.line.extend_from_slice(&self.buf[self.start..end]);
+            self.start = end;
+            self.scan = end;
+            let trim = match self.trim {
+                Trim::None => false,
+                Trim::Headers => self.in_header(),
+                Trim::Fields => !self.in_header(),
+                Trim::All => true,
+            };
+            match self.parser.feed(&mut self.line, &mut self.record, trim)? {
+                Feed::Eof => return Ok(Parsed::End),
+                Feed::Record => {
+                    std::mem::swap(record, &mut self.record);
+                    return Ok(Parsed::Record);
+                }
+                Feed::Skip | Feed::More => {}
+            }
+        }
+    }
+
+    fn in_header(&self) -> bool {
+        self.header && self.parser.num_record == 0
+    }
+}
+
+enum Unquoted<'a> {
+    Comma(&'a [u8]),
+    End,
+    More,
+}
+
+// The record parser shared by every reader. It is fed one line at a time, as
+// read up to the terminator, and keeps an open quoted field across lines.
+pub(crate) struct LineParser {
+    pub(crate) comma: Vec<u8>,
+    pub(crate) quote: u8,
+    pub(crate) escape: Option<u8>,
+    pub(crate) double_quote: bool,
+    pub(crate) terminator: Terminator,
+    pub(crate) comment: Option<u8>,
+    pub(crate) allow_diff_field_num: bool,
+    pub(crate) lazy_quote: bool,
+    pub(crate) trim_quoted: bool,
+    pub(crate) max_record_bytes: Option<usize>,
+    pub(crate) max_fields: Option<usize>,
+    pub(crate) max_field_bytes: Option<usize>,
+
+    pub(crate) num_line: usize,
+    pub(crate) num_record: usize,
+    pub(crate) offset: usize,
+    pub(crate) field_per_record: usize,
+    line_start: usize,
+    record_bytes: usize,
+    pos: Position,
+    quoted: Option<Position>,
+    // the open field is unquoted, continued after an escaped line break
+    unquoted: bool,
+    trim: bool,
+}
+
+impl LineParser {
+    pub(crate) fn new() -> Self {
+        LineParser {
+            comma: vec![b','],
+            quote: b'"',
+            escape: None,
+            double_quote: true,
+            terminator: Terminator::CRLF,
+            comment: None,
+            allow_diff_field_num: false,
+            lazy_quote: false,
+            trim_quoted: false,
+            max_record_bytes: None,
+            max_fields: None,
+            max_field_bytes: None,
+
+            num_line: 0,
+            num_record: 0,
+            offset: 0,
+            field_per_record: 0,
+            line_start: 0,
+            record_bytes: 0,
+            pos: Position::default(),
+            quoted: None,
+            unquoted: false,
+            trim: false,
+        }
+    }
+
+    pub(crate) fn is_line_break(&self, b: u8) -> bool {
+        b == b'\n' || b == b'\r' || self.terminator == Terminator::Any(b)
+    }
+
+    pub(crate) fn term(&self) -> u8 {
+        match self.terminator {
+            Terminator::CRLF => b'\n',
+            Terminator::Any(t) => t,
+        }
+    }
+
+    // Whether the next line belongs to the record being parsed.
+    pub(crate) fn in_record(&self) -> bool {
+        self.quoted.is_some()
+    }
+
+    pub(crate) fn reset(&mut self, pos: &Position) {
+        self.num_line = pos.line.saturating_sub(1);
+        self.num_record = pos.record;
+        self.offset = pos.byte;
+        self.field_per_record = 0;
+        self.quoted = None;
+        self.unquoted = false;
+    }
+
+    // Bytes dropped without being parsed, `end` if they end a line.
+    pub(crate) fn skip(&mut self, len: usize, end: bool) {
+        self.offset += len;
+        self.num_line += end as usize;
+        self.quoted = None;
+        self.unquoted = false;
+    }
+
+    // How many bytes the next line may take, one more than the record limit
+    // allows so that going over it shows.
+    pub(crate) fn line_limit(&self) -> u64 {
+        match self.max_record_bytes {
+            None => u64::MAX,
+            Some(max) if self.in_record() => (max - self.record_bytes.min(max)) as u64 + 1,
+            Some(max) => max as u64 + 1,
+        }
+    }
+
+    pub(crate) fn check_record_bytes(&self, len: usize, record: &ByteRecord) -> Result<()> {
+        let Some(max) = self.max_record_bytes else {
+            return Ok(());
+        };
+        let (before, pos) = match self.in_record() {
+            true => (self.record_bytes, record.position().cloned()),
+            false => (0, None),
+        };
+        if before + len <= max {
+            return Ok(());
+        }
+        let pos =
+            pos.unwrap_or_else(|| Position::new(self.offset, self.num_line + 1, self.num_record));
+        Err(Error::ErrLimit {
+            pos,
+            limit: Limit::RecordBytes,
+            max,
+        })
+    }
+
+    fn check_fields(&self, record: &ByteRecord, open: Option<&Position>) -> Result<()> {
+        if let Some(max) = self.max_fields
+            && record.len() + open.is_some() as usize > max
+        {
+            let pos = record.field_position(max).or(open).cloned();
+            return Err(Error::ErrLimit {
+                pos: pos.unwrap_or_default(),
+                limit: Limit::Fields,
+                max,
+            });
+        }
+        let Some(max) = self.max_field_bytes else {
+            return Ok(());
+        };
+        let pos = match record.iter().position(|field| field.len() > max) {
+            Some(i) => record.field_position(i),
+            None => open.filter(|_| record.open_field_len() > max),
+        };
+        match pos {
+            None => Ok(()),
+            Some(pos) => Err(Error::ErrLimit {
+                pos: pos.clone(),
+                limit: Limit::FieldBytes,
+                max,
+            }),
+        }
+    }
+
+    // `line` is empty at EOF, otherwise it ends with the terminator unless it
+    // is the last line of the input. `trim` applies if it starts a record.
+    pub(crate) fn feed(
+        &mut self,
+        line: &mut Vec<u8>,
+        record: &mut ByteRecord,
+        trim: bool,
+    ) -> Result<Feed> {
+        if let Err(err) = self.check_record_bytes(line.len(), record) {
+            self.quoted = None;
+            self.unquoted = false;
+            return Err(err);
+        }
+        self.record_bytes = match self.in_record() {
+            true => self.record_bytes + line.len(),
+            false => line.len(),
+        };
+        self.normalize(line);
+        let ret = self.parse_line(line, record, trim);
+        if ret.is_err() {
+            self.quoted = None;
+            self.unquoted = false;
+        }
+        ret
+    }
+
+    fn normalize(&mut self, line: &mut Vec<u8>) {
+        if let Terminator::Any(t) = self.terminator
+            && t != b'\n'
+            && !self.in_record()
+            && line.first() == Some(&b'\n')
+        {
+            // a line break right after the terminator belongs to it (`;\n`)
+            line.remove(0);
+            self.offset += 1;
+        }
+        if self.terminator == Terminator::CRLF && line.last() == Some(&b'\r') {
+            // unterminated last line
+            line.pop();
+        }
+        self.line_start = self.offset;
+        self.num_line += 1;
+        self.offset += line.len();
+    }
+
+    // Length of the line break ending `b`. A `\r\n` inside a quoted field is
+    // kept as is, only the one ending the record is dropped.
+    fn length_nl(&self, b: &[u8]) -> usize {
+        match b {
+            [.., b'\r', b'\n'] if self.terminator == Terminator::CRLF => 2,
+            [.., last] if *last == self.term() => 1,
+            _ => 0,
+        }
+    }
+
+    fn find_comma(&self, line: &[u8]) -> Option<usize> {
+        match self.comma.as_slice() {
+            [comma] => memchr(*comma, line),
+            comma => memmem::find(line, comma),
+        }
+    }
+
+    // The byte after an escape is kept as is, except MySQL's `\0` for NUL.
+    // `\N` (NULL) stays two bytes so that it can be matched as a null value.
+    fn push_escaped(record: &mut ByteRecord, escape: u8, ch: u8) {
+        match ch {
+            b'0' => record.push_byte(0),
+            b'N' => record.extend_field(&[escape, ch]),
+            ch => record.push_byte(ch),
+        }
+    }
+
+    // An unquoted field with escapes (MySQL `SELECT ... INTO OUTFILE` style),
+    // see `push_escaped`. An escaped line break leaves the field open for the
+    // next line.
+    fn parse_unquoted<'a>(
+        &self,
+        mut line: &'a [u8],
+        pos: &mut Position,
+        record: &mut ByteRecord,
+    ) -> Result<Unquoted<'a>> {
+        let term = self.term();
+        let escape = self.escape.unwrap_or(self.quote);
+        loop {
+            let comma = self.find_comma(line);
+            let end = comma.unwrap_or(line.len() - self.length_nl(line));
+            let i = memchr(escape, &line[..end]);
+            let field = &line[..i.unwrap_or(end)];
+            // Check to make sure a quote does not appear in field.
+            if !self.lazy_quote
+                && let Some(j) = memchr(self.quote, field)
+            {
+                return Err(Error::ErrQuote(self.num_line, pos.col + j));
+            }
+            record.extend_field(field);
+            let Some(i) = i else {
+                return Ok(match comma {
+                    Some(comma) => {
+                        pos.col += comma + self.comma.len();
+                        Unquoted::Comma(&line[comma + self.comma.len()..])
+                    }
+                    None => {
+                        pos.col += end;
+                        Unquoted::End
+                    }
+                });
+            };
+            match line.get(i + ESCAPE_LEN).filter(|&&ch| ch != term) {
+                Some(&ch) => {
+                    Self::push_escaped(record, escape, ch);
+                    line = &line[i + ESCAPE_LEN + 1..];
+                    pos.col += i + ESCAPE_LEN + 1;
+                }
+                None => {
+                    // escaped line break, or a dangling escape at EOF
+                    let rest = &line[i + ESCAPE_LEN..];
+                    record.extend_field(rest);
+                    pos.col += i + ESCAPE_LEN + rest.len();
+                    return Ok(match rest.is_empty() {
+                        true => Unquoted::End,
+                        false => Unquoted::More,
+                    });
+                }
+            }
+        }
+    }
+
+    fn parse_line(&mut self, mut line: &[u8], record: &mut ByteRecord, trim: bool) -> Result<Feed> {
+        let term = self.term();
+        let mut quoted = self.quoted.take();
+        if quoted.is_some() {
+            if !line.is_empty() {
+                self.pos.line += 1;
+                self.pos.col = 1;
+            }
+        } else if line.is_empty() {
+            record.clear();
+            return Ok(Feed::Eof);
+        } else if line.len() == self.length_nl(line) || line.first() == self.comment.as_ref() {
+            // skip empty line and comment line
+            return Ok(Feed::Skip);
+        } else {
+            record.clear();
+            self.trim = trim;
+            self.pos = Position {
+                byte: self.line_start,
+                line: self.num_line,
+                col: 1,
+                record: self.num_record,
+            };
+            record.set_position(self.pos.clone());
+            self.num_record += 1;
+        }
+        let trim = self.trim;
+        let line_start = self.line_start;
+        let mut pos = self.pos.clone();
+
+        'PARSE_FIELD: loop {
+            let (field_pos, unquoted) = match quoted.take() {
+                Some(field_pos) => (field_pos, std::mem::take(&mut self.unquoted)),
+                None => {
+                    if trim {
+                        // leading spaces before an opening quote
+                        let n = length_space(line);
+                        if line.get(n) == Some(&self.quote) {
+                            line = &line[n..];
+                            pos.col += n;
+                        }
+                    }
+                    let field_pos = Position {
+                        byte: line_start + pos.col - 1,
+                        ..pos.clone()
+                    };
+                    if line.first() != Some(&self.quote) && self.escape.is_some() {
+                        // No quote field with escapes, see `parse_unquoted`
+                        (field_pos, true)
+                    } else if line.first() != Some(&self.quote) {
+                        // No quote field
+                        let i = self.find_comma(line);
+                        let field = match i {
+                            None => &line[0..line.len() - self.length_nl(line)],
+                            Some(i) => &line[0..i],
+                        };
+                        // Check to make sure a quote does not appear in field.
+                        if !self.lazy_quote
+                            && let Some(j) = memchr(self.quote, field)
+                        {
+                            let col = pos.col + j;
+                            return Err(Error::ErrQuote(self.num_line, col));
+                        }
+                        record.extend_field(if trim { trim_bytes(field) } else { field });
+                        record.end_field(field_pos);
+                        if let Some(i) = i {
+                            line = &line[i + self.comma.len()..];
+                            pos.col += i + self.comma.len();
+                            continue 'PARSE_FIELD;
+                        }
+                        break 'PARSE_FIELD;
+                    } else {
+                        // Quote field
+                        line = &line[QUOTE_LEN..];
+                        pos.col += QUOTE_LEN;
+                        (field_pos, false)
+                    }
+                }
+            };
+            if unquoted {
+                let rest = match self.parse_unquoted(line, &mut pos, record)? {
+                    Unquoted::More => {
+                        self.check_fields(record, Some(&field_pos))?;
+                        self.pos = pos;
+                        self.quoted = Some(field_pos);
+                        self.unquoted = true;
+                        return Ok(Feed::More);
+                    }
+                    Unquoted::Comma(rest) => Some(rest),
+                    Unquoted::End => None,
+                };
+                if trim {
+                    record.trim_open_field();
+                }
+                record.end_field(field_pos);
+                match rest {
+                    Some(rest) => {
+                        line = rest;
+                        continue 'PARSE_FIELD;
+                    }
+                    None => break 'PARSE_FIELD,
+                }
+            }
+            loop {
+                let i = match self.escape {
+                    None => memchr(self.quote, line), //next quote
+                    Some(escape) => memchr2(self.quote, escape, line),
+                };
+                if let Some(i) = i
+                    && self.escape == Some(line[i])
+                {
+                    // Hit escape (see `push_escaped`)
+                    record.extend_field(&line[0..i]);
+                    match line.get(i + ESCAPE_LEN).filter(|&&ch| ch != term) {
+                        None => {
+                            // escaped line break is copied by the end of line branch
+                            line = &line[i + ESCAPE_LEN..];
+                            pos.col += i + ESCAPE_LEN;
+                        }
+                        Some(&ch) => {
+                            Self::push_escaped(record, line[i], ch);
+                            line = &line[i + ESCAPE_LEN + 1..];
+                            pos.col += i + ESCAPE_LEN + 1;
+                        }
+                    }
+                } else if let Some(i) = i {
+                    // Hit next quote
+                    record.extend_field(&line[0..i]);
+                    line = &line[i + QUOTE_LEN..];
+                    pos.col += i + QUOTE_LEN;
+                    if trim {
+                        // trailing spaces after a closing quote
+                        let n = length_space(line);
+                        let rest = &line[n..];
+                        if n > 0
+                            && (rest.starts_with(&self.comma) || self.length_nl(rest) == rest.len())
+                        {
+                            line = rest;
+                            pos.col += n;
+                        }
+                    }
+                    let ch = if !line.is_empty() { line[0] } else { b'\0' };
+                    if ch == self.quote && self.double_quote {
+                        // `""` sequence (append quote)
+                        record.push_byte(self.quote);
+                        line = &line[QUOTE_LEN..];
+                        pos.col += QUOTE_LEN;
+                    } else if line.starts_with(&self.comma) {
+                        // `",` sequence (end of field)
+                        line = &line[self.comma.len()..];
+                        pos.col += self.comma.len();
+                        if trim && self.trim_quoted {
+                            record.trim_open_field();
+                        }
+                        record.end_field(field_pos.clone());
+                        continue 'PARSE_FIELD;
+                    } else if self.length_nl(line) == line.len() {
+                        // `"\n` sequence (end of line)
+                        if trim && self.trim_quoted {
+                            record.trim_open_field();
+                        }
+                        record.end_field(field_pos.clone());
+                        break 'PARSE_FIELD;
+                    } else if self.lazy_quote {
+                        // `"` sequence (bare quote)
+                        record.push_byte(self.quote);
+                    } else {
+                        // `"*` sequence (invalid non-escaped quote)
+                        return Err(Error::ErrQuote(self.num_line, pos.col - QUOTE_LEN));
+                    }
+                } else if !line.is_empty() {
+                    // Hit end of line (copy all data so far)
+                    record.extend_field(line);
+                    pos.col += line.len();
+                    self.check_fields(record, Some(&field_pos))?;
+                    self.pos = pos;
+                    self.quoted = Some(field_pos);
+                    return Ok(Feed::More);
+                } else {
+                    if !self.lazy_quote {
+                        return Err(Error::ErrQuote(pos.line, pos.col));
+                    }
+                    if trim && self.trim_quoted {
+                        record.trim_open_field();
+                    }
+                    record.end_field(field_pos);
+                    break 'PARSE_FIELD;
+                }
+            }
+        }
+
+        self.check_fields(record, None)?;
+        if self.allow_diff_field_num {
+            // do nothing
+        } else if self.field_per_record == 0 {
+            self.field_per_record = record.len();
+        } else if self.field_per_record != record.len() {
+            return Err(Error::ErrFieldNum(
+                self.num_line,
+                pos.col,
+                self.field_per_record,
+                record.len(),
+            ));
+        }
+
+        Ok(Feed::Record)
+    }
+}
+
+// The field quoting shared by every writer, it appends encoded records to a
+// buffer the writers then flush to their sink.
+pub(crate) struct Quoter {
+    pub(crate) comma: Vec<u8>,
+    pub(crate) quote: u8,
+    pub(crate) escape: Option<u8>,
+    pub(crate) double_quote: bool,
+    pub(crate) terminator: Terminator,
+    pub(crate) comment: Option<u8>,
+    pub(crate) style: QuoteStyle,
+}
+
+impl Quoter {
+    pub(crate) fn new() -> Self {
+        Quoter {
+            comma: vec![b','],
+            quote: b'"',
+            escape: None,
+            double_quote: true,
+            terminator: Terminator::Any(b'\n'),
+            comment: None,
+            style: QuoteStyle::Necessary,
+        }
+    }
+
+    pub(crate) fn is_line_break(&self, b: u8) -> bool {
+        b == b'\n' || b == b'\r' || self.terminator == Terminator::Any(b)
+    }
+
+    // A record without fields can not be written, it would read back as an
+    // empty line, which is skipped.
+    pub(crate) fn write_record<'a, I>(&self, record: I, out: &mut Vec<u8>) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut fields = record.into_iter().peekable();
+        if fields.peek().is_none() {
+            return Err(Error::ErrEmptyRecord);
+        }
+        let mut n = 0;
+        while let Some(field) = fields.next() {
+            if n > 0 {
+                out.extend_from_slice(&self.comma);
+            }
+            // a lone empty field is quoted whatever the style, so that the
+            // line is not empty
+            let lone_empty = n == 0 && field.is_empty() && fields.peek().is_none();
+            n += 1;
+            let quote = lone_empty
+                || match self.style {
+                    QuoteStyle::Always => true,
+                    QuoteStyle::Necessary => self.field_needs_quotes(field),
+                    QuoteStyle::NonNumeric => !is_numeric(field) || self.field_needs_quotes(field),
+                    QuoteStyle::Never => false,
+                };
+            if !quote {
+                match (self.style, self.escape) {
+                    (QuoteStyle::Never, Some(escape)) => self.write_escaped(field, escape, out),
+                    _ => out.extend_from_slice(field),
+                }
+                continue;
+            }
+            out.push(self.quote);
+            let mut start = 0;
+            for i in self.specials(field) {
+                out.extend_from_slice(&field[start..i]);
+                start = i + 1;
+                match field[i] {
+                    c if c == self.quote => {
+                        let escaped = match (self.double_quote, self.escape) {
+                            (false, Some(escape)) => [escape, c],
+                            _ => [c, c],
+                        };
+                        out.extend_from_slice(&escaped);
+                    }
+                    c => {
+                        out.extend_from_slice(&[c, c]);
+                    }
+                };
+            }
+            out.extend_from_slice(&field[start..]);
+            out.push(self.quote);
+        }
+        match self.terminator {
+            Terminator::CRLF => out.extend_from_slice(b"\r\n"),
+            Terminator::Any(t) => out.push(t),
+        }
+        Ok(())
+    }
+
+    // Positions of the bytes a quoted field can not hold as is, line breaks
+    // are written unchanged.
+    fn specials<'a>(&self, field: &'a [u8]) -> Box<dyn Iterator<Item = usize> + 'a> {
+        match self.escape {
+            None => Box::new(memchr_iter(self.quote, field)),
+            Some(escape) => Box::new(memchr2_iter(self.quote, escape, field)),
+        }
+    }
+
+    fn write_escaped(&self, field: &[u8], escape: u8, out: &mut Vec<u8>) {
+        for &b in field {
+            if b == escape || b == self.quote || self.is_line_break(b) || self.comma.contains(&b) {
+                out.push(escape);
+            }
+            out.push(b);
+        }
+    }
+
+    fn field_needs_quotes(&self, field: &[u8]) -> bool {
+        if self.comment.is_some() && field.first() == self.comment.as_ref() {
+            return true;
+        }
+        let comma = match self.comma.as_slice() {
+            [comma] => *comma,
+            _ => return self.field_needs_quotes_multi(field),
+        };
+        for &b in field {
+            if self.is_line_break(b) || b == self.quote || b == comma || Some(b) == self.escape {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn field_needs_quotes_multi(&self, field: &[u8]) -> bool {
+        if field
+            .iter()
+            .any(|&b| self.is_line_break(b) || b == self.quote || Some(b) == self.escape)
+            || memmem::find(field, &self.comma).is_some()
+        {
+            return true;
+        }
+        // The field's tail must not run into the following delimiter, e.g.
+        // `a|` followed by `||` would be read back as `a` and `|...`.
+        let tail = &field[field.len().saturating_sub(self.comma.len() - 1)..];
+        let mut probe = tail.to_vec();
+        probe.extend_from_slice(&self.comma);
+        memmem::find(&probe, &self.comma) != Some(tail.len())
+    }
+}
+
+// An optionally signed decimal number with an optional exponent.
+fn is_numeric(b: &[u8]) -> bool {
+    let digits = |b: &[u8]| b.iter().take_while(|c| c.is_ascii_digit()).count();
+    let b = b.strip_prefix(b"-").or(b.strip_prefix(b"+")).unwrap_or(b);
+    let int = digits(b);
+    let mut b = &b[int..];
+    let mut frac = 0;
+    if let Some(rest) = b.strip_prefix(b".") {
+        frac = digits(rest);
+        b = &rest[frac..];
+    }
+    if int + frac == 0 {
+        return false;
+    }
+    match b {
+        [] => true,
+        [b'e' | b'E', exp @ ..] => {
+            let exp = exp
+                .strip_prefix(b"-")
+                .or(exp.strip_prefix(b"+"))
+                .unwrap_or(exp);
+            !exp.is_empty() && digits(exp) == exp.len()
+        }
+        _ => false,
+    }
+}
+
+fn length_space(b: &[u8]) -> usize {
+    b.iter().take_while(|&&c| c == b' ' || c == b'\t').count()
+}
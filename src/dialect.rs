@@ -0,0 +1,142 @@
+use crate::{Terminator, err::*, reader::Trim};
+use encoding_rs::Encoding;
+
+// The format settings shared by `Reader` and `Writer`, so that a file can be
+// written back the same way it was read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dialect {
+    pub delimiter: Vec<u8>,
+    pub quote: u8,
+    /// Escape byte inside quoted fields, `None` when quotes are only doubled.
+    pub escape: Option<u8>,
+    pub double_quote: bool,
+    pub terminator: Terminator,
+    /// The first record is a header, read as such or written by the writer.
+    pub header: bool,
+    /// Only used by the reader.
+    pub trim: Trim,
+    /// Lines starting with this byte are skipped by the reader and fields
+    /// starting with it are quoted by the writer.
+    pub comment: Option<u8>,
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect::rfc4180()
+    }
+}
+
+impl Dialect {
+    pub fn rfc4180() -> Self {
+        Dialect {
+            delimiter: vec![b','],
+            quote: b'"',
+            escape: None,
+            double_quote: true,
+            terminator: Terminator::CRLF,
+            header: true,
+            trim: Trim::None,
+            comment: None,
+            encoding: None,
+        }
+    }
+
+    pub fn excel() -> Self {
+        Dialect::rfc4180()
+    }
+
+    pub fn excel_tab() -> Self {
+        Dialect {
+            delimiter: vec![b'\t'],
+            ..Dialect::excel()
+        }
+    }
+
+    pub fn unix() -> Self {
+        Dialect {
+            terminator: Terminator::Any(b'\n'),
+            ..Dialect::rfc4180()
+        }
+    }
+
+    // `COPY ... WITH (FORMAT csv)` with its default options.
+    pub fn postgres_copy() -> Self {
+        Dialect {
+            terminator: Terminator::Any(b'\n'),
+            header: false,
+            ..Dialect::rfc4180()
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: &[u8]) -> Self {
+        self.delimiter = delimiter.to_vec();
+        self
+    }
+
+    pub fn with_quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    pub fn with_escape(mut self, escape: Option<u8>) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    pub fn with_double_quote(mut self, double_quote: bool) -> Self {
+        self.double_quote = double_quote;
+        self
+    }
+
+    pub fn with_terminator(mut self, terminator: Terminator) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn with_trim(mut self, trim: Trim) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn with_comment(mut self, comment: Option<u8>) -> Self {
+        self.comment = comment;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    // Same rules as the `with_*` builders of `Reader` and `Writer`, checked
+    // all at once since a dialect may change several settings together.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let line_break = |b: u8| b == b'\n' || b == b'\r' || self.terminator == Terminator::Any(b);
+        if self.delimiter.is_empty()
+            || self
+                .delimiter
+                .iter()
+                .any(|&b| line_break(b) || b == self.quote || Some(b) == self.escape)
+        {
+            return Err(Error::ErrInvalidDelim);
+        }
+        if line_break(self.quote) || self.escape == Some(self.quote) {
+            return Err(Error::ErrInvalidQuote);
+        }
+        if self.escape.is_some_and(line_break) {
+            return Err(Error::ErrInvalidEscape);
+        }
+        if let Some(comment) = self.comment
+            && (line_break(comment) || comment == self.delimiter[0] || comment == self.quote)
+        {
+            return Err(Error::ErrInvalidDelim);
+        }
+        Ok(())
+    }
+}
@@ -1,38 +1,213 @@
+use crate::record::Position;
 use std::fmt::Display;
 
-#[derive(Debug, PartialEq)]
-pub enum ErrorKind {
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
     ErrInvalidDelim,
+    ErrInvalidQuote,
+    ErrInvalidEscape,
+    ErrInvalidTerminator,
     ErrEOF,
     ErrQuote(usize, usize),
     ErrChar(usize, usize, u8),
     ErrFieldNum(usize, usize, usize, usize),
     ErrMissField(String),
+    ErrEmptyRecord,
+    ErrIo(std::io::Error),
+    ErrUtf8 {
+        pos: Option<Position>,
+        field: usize,
+        err: std::str::Utf8Error,
+    },
+    ErrParse {
+        field: String,
+        value: String,
+        ty: &'static str,
+        err: Box<dyn std::error::Error + Send + Sync>,
+    },
+    ErrLimit {
+        pos: Position,
+        limit: Limit,
+        max: usize,
+    },
+    ErrSerde(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    RecordBytes,
+    Fields,
+    FieldBytes,
 }
 
-impl Display for ErrorKind {
+impl Display for Limit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ErrorKind::ErrInvalidDelim => write!(f, "Invalid Delimiter"),
-            ErrorKind::ErrEOF => write!(f, "EOF"),
-            ErrorKind::ErrQuote(line, col) => {
+            Limit::RecordBytes => write!(f, "record bytes"),
+            Limit::Fields => write!(f, "fields"),
+            Limit::FieldBytes => write!(f, "field bytes"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    #[default]
+    FailFast,
+    /// Drop the bad record and pass it to the reader's `on_bad_record` callback.
+    Skip,
+    /// Drop the bad record and keep it in the reader's `bad_records`.
+    Collect,
+}
+
+#[derive(Debug)]
+pub struct BadRecord {
+    pub position: Position,
+    pub raw: Vec<u8>,
+    pub error: Error,
+}
+
+impl Error {
+    /// Whether a reader can drop the bad record and go on with the next one.
+    /// A record over the `RecordBytes` limit may not be read to its end, so
+    /// that limit is fatal.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Error::ErrQuote(..)
+                | Error::ErrFieldNum(..)
+                | Error::ErrUtf8 { .. }
+                | Error::ErrLimit {
+                    limit: Limit::Fields | Limit::FieldBytes,
+                    ..
+                }
+        )
+    }
+
+    pub fn parse<E>(field: &str, value: &str, ty: &'static str, err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error::ErrParse {
+            field: field.to_string(),
+            value: value.to_string(),
+            ty,
+            err: err.into(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ErrInvalidDelim => write!(f, "Invalid Delimiter"),
+            Error::ErrInvalidQuote => write!(f, "Invalid Quote"),
+            Error::ErrInvalidEscape => write!(f, "Invalid Escape"),
+            Error::ErrInvalidTerminator => write!(f, "Invalid Terminator"),
+            Error::ErrEOF => write!(f, "EOF"),
+            Error::ErrQuote(line, col) => {
                 write!(f, "line:{} col:{} Error Quote", line, col)
             }
-            ErrorKind::ErrChar(line, col, ch) => {
+            Error::ErrChar(line, col, ch) => {
                 write!(f, "line:{} col:{} Unexpected Character {}", line, col, ch)
             }
-            ErrorKind::ErrFieldNum(line, col, expect, got) => {
+            Error::ErrFieldNum(line, col, expect, got) => {
                 write!(
                     f,
                     "line:{} col:{} Wrong Number Of Fields, Expect:{} Got:{}",
                     line, col, expect, got
                 )
             }
-            ErrorKind::ErrMissField(field) => {
+            Error::ErrMissField(field) => {
                 write!(f, "missing field {}", field)
             }
+            Error::ErrEmptyRecord => write!(f, "Empty Record"),
+            Error::ErrIo(err) => write!(f, "{}", err),
+            Error::ErrUtf8 { pos, field, err } => match pos {
+                Some(pos) => write!(
+                    f,
+                    "record:{} line:{} field:{} Invalid UTF-8: {}",
+                    pos.record(),
+                    pos.line(),
+                    field,
+                    err
+                ),
+                None => write!(f, "field:{} Invalid UTF-8: {}", field, err),
+            },
+            Error::ErrParse {
+                field,
+                value,
+                ty,
+                err,
+            } => {
+                write!(
+                    f,
+                    "field {} can not parse {:?} as {}: {}",
+                    field, value, ty, err
+                )
+            }
+            Error::ErrLimit { pos, limit, max } => {
+                write!(
+                    f,
+                    "record:{} line:{} col:{} Too Many {}, Max:{}",
+                    pos.record(),
+                    pos.line(),
+                    pos.col(),
+                    limit,
+                    max
+                )
+            }
+            Error::ErrSerde(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ErrIo(err) => Some(err),
+            Error::ErrUtf8 { err, .. } => Some(err),
+            Error::ErrParse { err, .. } => Some(err.as_ref()),
+            _ => None,
         }
     }
 }
 
-impl std::error::Error for ErrorKind {}
+#[cfg(feature = "serde")]
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::ErrSerde(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error::ErrMissField(field.to_string())
+    }
+
+    // Fields reach serde as strings only when it buffers them, as it does for
+    // `#[serde(flatten)]`, and it can not parse them then.
+    fn invalid_type(unexp: serde::de::Unexpected, exp: &dyn serde::de::Expected) -> Self {
+        match unexp {
+            serde::de::Unexpected::Str(value) => Error::ErrSerde(format!(
+                "invalid type: string {:?}, expected {}, fields of a flattened struct \
+                 can only be strings unless `deserialize_with` parses them",
+                value, exp
+            )),
+            unexp => Error::ErrSerde(format!("invalid type: {}, expected {}", unexp, exp)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::ErrSerde(msg.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::ErrIo(err)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::err::*;
+use crate::reader::{ReaderState, reader_api, reader_stream_api};
+use crate::record::Position;
+use crate::writer::{WriterState, writer_api};
+use futures_util::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+    BufReader,
+};
+use std::io::SeekFrom;
+
+pub struct Reader<R: AsyncRead + std::marker::Unpin> {
+    r: BufReader<R>,
+    state: ReaderState,
+}
+
+impl<R: AsyncRead + std::marker::Unpin> Reader<R> {
+    pub fn new(r: R) -> Self {
+        Self {
+            r: BufReader::new(r),
+            state: ReaderState::new(),
+        }
+    }
+
+    reader_api!({async}, {.await});
+
+    reader_stream_api!();
+
+    async fn fill_line(&mut self) -> Result<()> {
+        self.state.line.clear();
+        let term = self.state.parser.term();
+        let limit = self.state.parser.line_limit();
+        (&mut self.r)
+            .take(limit)
+            .read_until(term, &mut self.state.line)
+            .await?;
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + std::marker::Unpin> Reader<R> {
+    pub async fn seek(&mut self, pos: Position) -> Result<()> {
+        self.r.seek(SeekFrom::Start(pos.byte as u64)).await?;
+        self.state.seek_to(&pos);
+        Ok(())
+    }
+}
+
+pub struct Writer<W> {
+    w: W,
+    state: WriterState,
+}
+
+impl<W: AsyncWrite + std::marker::Unpin> Writer<W> {
+    pub fn new(w: W) -> Self {
+        Writer {
+            w,
+            state: WriterState::new(),
+        }
+    }
+
+    writer_api!({async}, {.await});
+
+    async fn write_buf(&mut self) -> Result<()> {
+        self.w.write_all(&self.state.buf).await?;
+        self.state.buf.clear();
+        Ok(())
+    }
+
+    async fn flush_sink(&mut self) -> Result<()> {
+        self.w.flush().await?;
+        Ok(())
+    }
+
+    fn into_sink(self) -> W {
+        self.w
+    }
+}
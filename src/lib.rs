@@ -1,15 +1,43 @@
+pub mod core;
+#[cfg(feature = "serde")]
+mod deserializer;
+pub mod dialect;
 pub mod err;
+#[cfg(feature = "futures-io")]
+pub mod futures;
 pub mod reader;
+pub mod record;
+#[cfg(feature = "serde")]
+mod serializer;
+#[cfg(feature = "async")]
+pub mod sniff;
+#[cfg(any(feature = "async", feature = "futures-io"))]
+mod stream;
+pub mod sync;
 pub mod writer;
 
-use anyhow::Result;
+pub use err::{Error, Result};
+#[cfg(feature = "async")]
+pub use sniff::{Sniff, sniff};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Terminator {
+    /// `\n` or `\r\n` when reading, `\r\n` when writing. Line breaks inside
+    /// quoted fields are kept as is both ways.
+    #[default]
+    CRLF,
+    /// A single byte. When reading, one `\n` right after it is skipped so
+    /// that `;\n` style exports work; a record can therefore not start with
+    /// an unquoted `\n`. Only the byte itself is written.
+    Any(u8),
+}
 
 pub trait HeaderCSV {
     fn get_header() -> Vec<String>;
 }
 
 pub trait FromCSV: Sized {
-    fn from_csv(header: &Vec<String>, record: &Vec<String>) -> Result<Self>;
+    fn from_csv(header: &[String], record: &[String]) -> Result<Self>;
 }
 
 pub trait ToCSV {
@@ -1,351 +1,588 @@
-use crate::{FromCSV, HeaderCSV, err::*};
-use anyhow::Result;
+use crate::core::{Feed, LineParser};
+use crate::err::*;
+use crate::record::{ByteRecord, Position, StringRecord};
 use encoding_rs::Encoding;
-use memchr::memchr;
-use std::str::from_utf8;
-use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
-const COMMA_LEN: usize = 1;
-const QUOTE_LEN: usize = 1;
+#[cfg(feature = "async")]
+use std::io::SeekFrom;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, BufReader};
 
-#[derive(Clone)]
-struct Position {
-    line: usize,
-    col: usize,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Trim {
+    #[default]
+    None,
+    Headers,
+    Fields,
+    All,
 }
 
-#[derive(Default)]
-struct Slice {
-    line: Vec<u8>,
-    is_eof: bool,
-}
-
-struct Record {
-    fields: Vec<Vec<u8>>,
-    is_eof: bool,
-}
+// Everything a reader needs apart from its input.
+pub(crate) struct ReaderState {
+    pub(crate) parser: LineParser,
+    pub(crate) skip_header: bool,
+    pub(crate) has_header: bool,
+    pub(crate) custom_header: Option<Vec<String>>,
+    pub(crate) trim: Trim,
+    pub(crate) encoding: Option<&'static Encoding>,
+    pub(crate) error_policy: ErrorPolicy,
+    pub(crate) on_bad_record: Option<Box<dyn FnMut(BadRecord) + Send>>,
 
-pub struct Reader<R: AsyncRead + std::marker::Unpin> {
-    r: BufReader<R>,
-    comma: u8,
-    skip_header: bool,
-    custom_header: Option<Vec<String>>,
-    allow_diff_field_num: bool,
-    lazy_quote: bool,
-    encoding: Option<&'static Encoding>,
-
-    num_line: usize,
-    offset: usize,
-    field_per_record: usize,
-    still_skip_header: bool,
+    pub(crate) still_skip_header: bool,
+    pub(crate) in_header: bool,
+    pub(crate) headers: Option<Vec<String>>,
+    pub(crate) record: ByteRecord,
+    pub(crate) line: Vec<u8>,
+    raw: Vec<u8>,
+    pub(crate) bad_records: Vec<BadRecord>,
 }
 
-impl<R: AsyncRead + std::marker::Unpin> Reader<R> {
-    pub fn new(r: R) -> Self {
-        Self {
-            r: BufReader::new(r),
-            comma: b',',
+impl ReaderState {
+    pub(crate) fn new() -> Self {
+        ReaderState {
+            parser: LineParser::new(),
             skip_header: false,
+            has_header: false,
             custom_header: None,
-            allow_diff_field_num: false,
-            lazy_quote: false,
+            trim: Trim::None,
             encoding: None,
+            error_policy: ErrorPolicy::FailFast,
+            on_bad_record: None,
 
-            num_line: 0,
-            offset: 0,
-            field_per_record: 0,
             still_skip_header: false,
+            in_header: false,
+            headers: None,
+            record: ByteRecord::new(),
+            line: Vec::new(),
+            raw: Vec::new(),
+            bad_records: Vec::new(),
         }
     }
 
-    pub fn with_comma(mut self, comma: u8) -> Result<Self> {
-        match comma {
-            b'\n' | b'\r' | b'"' => Err(ErrorKind::ErrInvalidDelim.into()),
+    // Feeds `self.line` to the parser, keeping a copy of the raw record when
+    // bad records are not fatal.
+    pub(crate) fn feed(&mut self, record: &mut ByteRecord) -> Result<Feed> {
+        let keep_raw = self.error_policy != ErrorPolicy::FailFast;
+        if keep_raw && !self.parser.in_record() {
+            self.raw.clear();
+        }
+        let trim = self.trim_row();
+        let ret = self.parser.feed(&mut self.line, record, trim);
+        if keep_raw {
+            self.raw.extend_from_slice(&self.line);
+        }
+        ret
+    }
+
+    pub(crate) fn handle_bad_record(&mut self, position: Position, error: Error) -> Result<()> {
+        if self.error_policy == ErrorPolicy::FailFast {
+            return Err(error);
+        }
+        let bad = BadRecord {
+            position,
+            raw: std::mem::take(&mut self.raw),
+            error,
+        };
+        match self.error_policy {
+            ErrorPolicy::Collect => self.bad_records.push(bad),
             _ => {
-                self.comma = comma;
-                Ok(self)
+                if let Some(f) = self.on_bad_record.as_mut() {
+                    f(bad);
+                }
             }
         }
+        Ok(())
     }
 
-    pub fn with_skip_header(mut self, skip_header: bool) -> Self {
-        self.skip_header = skip_header;
-        self.still_skip_header = skip_header;
-        self
+    pub(crate) fn set_headers(&mut self, record: &ByteRecord) -> Result<()> {
+        let mut header = StringRecord::new();
+        header.decode_from(record, self.encoding)?;
+        self.headers = Some(to_strings(&header));
+        Ok(())
     }
 
-    pub fn with_custom_header(mut self, custom_header: Vec<String>) -> Self {
-        self.custom_header = Some(custom_header.clone());
-        self
+    pub(crate) fn seek_to(&mut self, pos: &Position) {
+        self.parser.reset(pos);
+        if pos.record == 0 {
+            self.still_skip_header = self.skip_header || self.has_header;
+            self.headers = None;
+        } else {
+            self.still_skip_header = false;
+        }
     }
 
-    pub fn with_allow_diff_field_num(mut self, allow_diff_field_num: bool) -> Self {
-        self.allow_diff_field_num = allow_diff_field_num;
-        self
+    fn trim_row(&self) -> bool {
+        match self.trim {
+            Trim::None => false,
+            Trim::Headers => self.in_header,
+            Trim::Fields => !self.in_header,
+            Trim::All => true,
+        }
     }
+}
 
-    pub fn with_lazy_quote(mut self, lazy_quote: bool) -> Self {
-        self.lazy_quote = lazy_quote;
-        self
-    }
+// The reader API shared by the blocking and async readers. `$async` and
+// `$await` are either empty or `async` and `.await`, the reader provides
+// `fill_line` to read the next line into `self.state.line`.
+macro_rules! reader_api {
+    ({$($async:tt)*}, {$($await:tt)*}) => {
+        pub fn with_comma(self, comma: u8) -> $crate::Result<Self> {
+            self.with_delimiter(&[comma])
+        }
 
-    pub fn with_encoding(mut self, encoding: &'static Encoding) -> Self {
-        self.encoding = Some(encoding);
-        self
-    }
+        pub fn with_delimiter(mut self, delimiter: &[u8]) -> $crate::Result<Self> {
+            let p = &self.state.parser;
+            let invalid = |b: &u8| p.is_line_break(*b) || *b == p.quote || Some(*b) == p.escape;
+            if delimiter.is_empty()
+                || delimiter.iter().any(invalid)
+                || p.comment.as_ref() == delimiter.first()
+            {
+                return Err($crate::Error::ErrInvalidDelim);
+            }
+            self.state.parser.comma = delimiter.to_vec();
+            Ok(self)
+        }
 
-    pub async fn deserialize<T>(&mut self) -> Result<Vec<T>>
-    where
-        T: HeaderCSV + FromCSV,
-    {
-        let string_records = self.string_records().await?;
-        let mut ret = Vec::new();
-        for record in string_records {
-            ret.push(T::from_csv(
-                self.custom_header
-                    .as_ref()
-                    .unwrap_or(T::get_header().as_ref())
-                    .as_ref(),
-                &record,
-            )?);
-        }
-        Ok(ret)
-    }
+        pub fn with_quote(mut self, quote: u8) -> $crate::Result<Self> {
+            let p = &mut self.state.parser;
+            match quote {
+                _ if p.is_line_break(quote) => Err($crate::Error::ErrInvalidQuote),
+                _ if p.comma.contains(&quote) || p.escape == Some(quote) => {
+                    Err($crate::Error::ErrInvalidQuote)
+                }
+                _ if p.comment == Some(quote) => Err($crate::Error::ErrInvalidQuote),
+                _ => {
+                    p.quote = quote;
+                    Ok(self)
+                }
+            }
+        }
 
-    pub async fn string_records(&mut self) -> Result<Vec<Vec<String>>> {
-        let records = self.bytes_records().await?;
-        let mut ret = Vec::new();
-        let is_none = self.encoding.is_none();
-        let encoding = self.encoding.unwrap_or(encoding_rs::UTF_8);
-        for record in records {
-            let mut fields = Vec::new();
-            for f in record {
-                fields.push(if is_none {
-                    to_utf8(&f)?
-                } else {
-                    to_encoding(&f, encoding)?
-                });
+        pub fn with_escape(mut self, escape: Option<u8>) -> $crate::Result<Self> {
+            let p = &mut self.state.parser;
+            match escape {
+                Some(e) if p.is_line_break(e) => Err($crate::Error::ErrInvalidEscape),
+                Some(e) if p.comma.contains(&e) || e == p.quote => {
+                    Err($crate::Error::ErrInvalidEscape)
+                }
+                _ => {
+                    p.escape = escape;
+                    Ok(self)
+                }
             }
-            ret.push(fields);
         }
-        Ok(ret)
-    }
 
-    pub async fn bytes_records(&mut self) -> Result<Vec<Vec<Vec<u8>>>> {
-        let mut records = Vec::new();
-        loop {
-            let record = self.read_record().await?;
-            if record.is_eof {
-                break;
-            } else if self.still_skip_header {
-                self.still_skip_header = false;
-            } else {
-                records.push(record.fields);
+        pub fn with_double_quote(mut self, double_quote: bool) -> Self {
+            self.state.parser.double_quote = double_quote;
+            self
+        }
+
+        pub fn with_terminator(mut self, terminator: $crate::Terminator) -> $crate::Result<Self> {
+            let p = &mut self.state.parser;
+            if let $crate::Terminator::Any(t) = terminator
+                && (p.comma.contains(&t)
+                    || t == p.quote
+                    || p.escape == Some(t)
+                    || p.comment == Some(t))
+            {
+                return Err($crate::Error::ErrInvalidTerminator);
             }
+            p.terminator = terminator;
+            Ok(self)
         }
-        Ok(records)
-    }
 
-    async fn read_record<'a>(&mut self) -> Result<Record> {
-        let mut record_buf = Vec::new();
-        let mut field_index = Vec::new();
-        let mut field_position = Vec::new();
-        let mut s = Slice::default();
-        // skip empty line
-        while !s.is_eof {
-            s = self.read_line().await?;
-            if s.line.len() == length_nl(&s.line) {
-                s.line.clear();
-                continue;
+        pub fn with_comment(mut self, comment: u8) -> $crate::Result<Self> {
+            let p = &mut self.state.parser;
+            match comment {
+                _ if p.is_line_break(comment) => Err($crate::Error::ErrInvalidDelim),
+                _ if p.comma[0] == comment || comment == p.quote => {
+                    Err($crate::Error::ErrInvalidDelim)
+                }
+                _ => {
+                    p.comment = Some(comment);
+                    Ok(self)
+                }
             }
-            break;
         }
-        if s.is_eof {
-            return Ok(Record {
-                fields: Vec::new(),
-                is_eof: true,
-            });
+
+        pub fn with_skip_header(mut self, skip_header: bool) -> Self {
+            self.state.skip_header = skip_header;
+            self.state.still_skip_header = self.state.skip_header || self.state.has_header;
+            self
         }
 
-        let Slice { line, is_eof } = s;
-        let mut line = line.as_slice();
-        let mut pos = Position {
-            line: self.num_line,
-            col: 1,
-        };
-        'PARSE_FIELD: loop {
-            if line.len() == 0 || line[0] != b'"' {
-                // No quote field
-                let i = memchr(self.comma, &line);
-                let field = match i {
-                    None => &line[0..line.len() - length_nl(&line)],
-                    Some(i) => &line[0..i],
-                };
-                // Check to make sure a quote does not appear in field.
-                if !self.lazy_quote {
-                    if let Some(j) = memchr(b'"', field) {
-                        let col = pos.col + j;
-                        return Err(ErrorKind::ErrQuote(self.num_line, col).into());
-                    }
-                }
-                record_buf.extend_from_slice(field);
-                field_index.push(record_buf.len());
-                field_position.push(pos.clone());
-                if let Some(i) = i {
-                    line = &line[i + COMMA_LEN..];
-                    pos.col += i + COMMA_LEN;
-                    continue 'PARSE_FIELD;
+        pub fn with_has_header(mut self, has_header: bool) -> Self {
+            self.state.has_header = has_header;
+            self.state.still_skip_header = self.state.skip_header || self.state.has_header;
+            self
+        }
+
+        pub fn with_custom_header(mut self, custom_header: Vec<String>) -> Self {
+            self.state.custom_header = Some(custom_header);
+            self
+        }
+
+        pub fn with_allow_diff_field_num(mut self, allow_diff_field_num: bool) -> Self {
+            self.state.parser.allow_diff_field_num = allow_diff_field_num;
+            self
+        }
+
+        pub fn with_lazy_quote(mut self, lazy_quote: bool) -> Self {
+            self.state.parser.lazy_quote = lazy_quote;
+            self
+        }
+
+        pub fn with_trim(mut self, trim: $crate::reader::Trim) -> Self {
+            self.state.trim = trim;
+            self
+        }
+
+        pub fn with_trim_quoted(mut self, trim_quoted: bool) -> Self {
+            self.state.parser.trim_quoted = trim_quoted;
+            self
+        }
+
+        /// Fails with `ErrLimit` once a record, terminators included, takes more
+        /// than `max` bytes, without buffering the rest of it.
+        pub fn with_max_record_bytes(mut self, max: usize) -> Self {
+            self.state.parser.max_record_bytes = Some(max);
+            self
+        }
+
+        pub fn with_max_fields(mut self, max: usize) -> Self {
+            self.state.parser.max_fields = Some(max);
+            self
+        }
+
+        pub fn with_max_field_bytes(mut self, max: usize) -> Self {
+            self.state.parser.max_field_bytes = Some(max);
+            self
+        }
+
+        pub fn with_encoding(mut self, encoding: &'static ::encoding_rs::Encoding) -> Self {
+            self.state.encoding = Some(encoding);
+            self
+        }
+
+        pub fn with_dialect(mut self, dialect: $crate::dialect::Dialect) -> $crate::Result<Self> {
+            dialect.validate()?;
+            let p = &mut self.state.parser;
+            p.comma = dialect.delimiter;
+            p.quote = dialect.quote;
+            p.escape = dialect.escape;
+            p.double_quote = dialect.double_quote;
+            p.terminator = dialect.terminator;
+            p.comment = dialect.comment;
+            self.state.trim = dialect.trim;
+            self.state.encoding = dialect.encoding;
+            Ok(self.with_has_header(dialect.header))
+        }
+
+        pub fn with_error_policy(mut self, error_policy: $crate::err::ErrorPolicy) -> Self {
+            self.state.error_policy = error_policy;
+            self
+        }
+
+        pub fn with_on_bad_record<F>(mut self, on_bad_record: F) -> Self
+        where
+            F: FnMut($crate::err::BadRecord) + Send + 'static,
+        {
+            self.state.on_bad_record = Some(Box::new(on_bad_record));
+            self
+        }
+
+        pub fn bad_records(&self) -> &[$crate::err::BadRecord] {
+            &self.state.bad_records
+        }
+
+        pub fn take_bad_records(&mut self) -> Vec<$crate::err::BadRecord> {
+            std::mem::take(&mut self.state.bad_records)
+        }
+
+        pub fn position(&self) -> $crate::record::Position {
+            $crate::record::Position::new(
+                self.state.parser.offset,
+                self.state.parser.num_line + 1,
+                self.state.parser.num_record,
+            )
+        }
+
+        pub $($async)* fn deserialize<T>(&mut self) -> $crate::Result<Vec<T>>
+        where
+            T: $crate::HeaderCSV + $crate::FromCSV,
+        {
+            let header = self.header_for::<T>() $($await)* ?;
+            let mut ret = Vec::new();
+            while let Some(record) = self.next_string_record() $($await)* ? {
+                ret.push(T::from_csv(&header, &$crate::reader::to_strings(&record))?);
+            }
+            Ok(ret)
+        }
+
+        /// Maps header columns to the fields of `T`, or takes them in order if
+        /// there is no header. Fields of a `#[serde(flatten)]` struct are all
+        /// given as strings, other types need a `deserialize_with` that
+        /// parses them.
+        #[cfg(feature = "serde")]
+        pub $($async)* fn deserialize_serde<T>(&mut self) -> $crate::Result<Vec<T>>
+        where
+            T: ::serde::de::DeserializeOwned,
+        {
+            let header = match &self.state.custom_header {
+                Some(header) => Some(header.clone()),
+                None => self.headers() $($await)* ?.map(<[String]>::to_vec),
+            };
+            let mut ret = Vec::new();
+            while let Some(record) = self.next_string_record() $($await)* ? {
+                ret.push($crate::deserializer::from_record(header.as_deref(), &record)?);
+            }
+            Ok(ret)
+        }
+
+        pub $($async)* fn string_records(&mut self) -> $crate::Result<Vec<Vec<String>>> {
+            let mut records = Vec::new();
+            while let Some(record) = self.next_string_record() $($await)* ? {
+                records.push($crate::reader::to_strings(&record));
+            }
+            Ok(records)
+        }
+
+        pub $($async)* fn bytes_records(&mut self) -> $crate::Result<Vec<Vec<Vec<u8>>>> {
+            let mut records = Vec::new();
+            while let Some(record) = self.next_bytes_record() $($await)* ? {
+                records.push(record);
+            }
+            Ok(records)
+        }
+
+        pub $($async)* fn headers(&mut self) -> $crate::Result<Option<&[String]>> {
+            if self.state.still_skip_header {
+                self.read_header() $($await)* ?;
+            }
+            Ok(self.state.headers.as_deref())
+        }
+
+        $($async)* fn header_for<T: $crate::HeaderCSV>(&mut self) -> $crate::Result<Vec<String>> {
+            if let Some(header) = &self.state.custom_header {
+                return Ok(header.clone());
+            }
+            match self.headers() $($await)* ? {
+                Some(header) => Ok(header.to_vec()),
+                None => Ok(T::get_header()),
+            }
+        }
+
+        $($async)* fn read_header(&mut self) -> $crate::Result<()> {
+            self.state.still_skip_header = false;
+            let mut record = $crate::record::ByteRecord::new();
+            self.state.in_header = true;
+            let ret = self.parse_record(&mut record) $($await)*;
+            self.state.in_header = false;
+            match ret {
+                // a bad header goes to the error policy and `headers` stays
+                // `None`, the next row is not taken in its place
+                Err(err) if err.is_recoverable() => {
+                    let pos = record.position().cloned().unwrap_or_default();
+                    self.state.handle_bad_record(pos, err)
                 }
-                break 'PARSE_FIELD;
-            } else {
-                // Quote field
-                let field_pos = Position {
-                    line: pos.line,
-                    col: pos.col,
-                };
-                line = &line[QUOTE_LEN..];
-                pos.col += QUOTE_LEN;
-                loop {
-                    let i = memchr(b'"', &line); //next quote
-                    if let Some(i) = i {
-                        // Hit next quote
-                        record_buf.extend_from_slice(&line[0..i]);
-                        line = &line[i + QUOTE_LEN..];
-                        pos.col += i + QUOTE_LEN;
-                        let ch = if line.len() > 0 { line[0] } else { b'\0' };
-                        if ch == b'"' {
-                            // `""` sequence (append quote)
-                            record_buf.push(b'"');
-                            line = &line[QUOTE_LEN..];
-                            pos.col += QUOTE_LEN;
-                        } else if ch == self.comma {
-                            // `",` sequence (end of field)
-                            line = &line[COMMA_LEN..];
-                            pos.col += COMMA_LEN;
-                            field_index.push(record_buf.len());
-                            field_position.push(field_pos.clone());
-                            continue 'PARSE_FIELD;
-                        } else if length_nl(line) == line.len() {
-                            // `"\n` sequence (end of line)
-                            field_index.push(record_buf.len());
-                            field_position.push(field_pos.clone());
-                            break 'PARSE_FIELD;
-                        } else if self.lazy_quote {
-                            // `"` sequence (bare quote)
-                            record_buf.push(b'"');
-                        } else {
-                            // `"*` sequence (invalid non-escaped quote)
-                            return Err(
-                                ErrorKind::ErrQuote(self.num_line, pos.col - QUOTE_LEN).into()
-                            );
-                        }
-                    } else if line.len() > 0 {
-                        // Hit end of line (copy all data so far)
-                        record_buf.extend_from_slice(line);
-                        pos.col += line.len();
-                        s = self.read_line().await?;
-                        line = s.line.as_slice();
-                        if line.len() > 0 {
-                            pos.line += 1;
-                            pos.col = 1;
-                        }
-                        if s.is_eof {
-                            s.is_eof = false;
-                        }
-                    } else {
-                        if !self.lazy_quote {
-                            return Err(ErrorKind::ErrQuote(pos.line, pos.col).into());
-                        }
-                        field_index.push(record_buf.len());
-                        field_position.push(field_pos);
-                        break 'PARSE_FIELD;
+                ret => {
+                    if ret? && self.state.has_header {
+                        self.state.set_headers(&record)?;
                     }
+                    Ok(())
                 }
             }
         }
 
-        if self.allow_diff_field_num {
-            // do nothing
-        } else if self.field_per_record == 0 {
-            self.field_per_record = field_index.len();
-        } else if self.field_per_record != field_index.len() {
-            return Err(ErrorKind::ErrFieldNum(
-                self.num_line,
-                pos.col,
-                self.field_per_record,
-                field_index.len(),
-            )
-            .into());
+        pub $($async)* fn read_byte_record(
+            &mut self,
+            record: &mut $crate::record::ByteRecord,
+        ) -> $crate::Result<bool> {
+            if self.state.still_skip_header {
+                self.read_header() $($await)* ?;
+            }
+            self.read_record(record) $($await)*
         }
 
-        let mut record = Record {
-            fields: Vec::new(),
-            is_eof: is_eof,
-        };
-        let mut pre_idx = 0;
-        for idx in field_index {
-            record.fields.push(record_buf[pre_idx..idx].to_vec());
-            pre_idx = idx;
+        pub $($async)* fn read_string_record(
+            &mut self,
+            record: &mut $crate::record::StringRecord,
+        ) -> $crate::Result<bool> {
+            loop {
+                let mut buf = std::mem::take(&mut self.state.record);
+                let ret = self.read_byte_record(&mut buf) $($await)*;
+                self.state.record = buf;
+                if !ret? {
+                    return Ok(false);
+                }
+                match record.decode_from(&self.state.record, self.state.encoding) {
+                    Ok(()) => return Ok(true),
+                    Err(err) => {
+                        let pos = self.state.record.position().cloned().unwrap_or_default();
+                        self.state.handle_bad_record(pos, err)?;
+                    }
+                }
+            }
         }
 
-        Ok(record)
-    }
+        $($async)* fn next_string_record(
+            &mut self,
+        ) -> $crate::Result<Option<$crate::record::StringRecord>> {
+            let mut record = $crate::record::StringRecord::new();
+            if !self.read_string_record(&mut record) $($await)* ? {
+                return Ok(None);
+            }
+            Ok(Some(record))
+        }
 
-    async fn read_line(&mut self) -> Result<Slice> {
-        let Slice {
-            mut line,
-            mut is_eof,
-        } = self.read_slice().await?;
-        let mut n = line.len();
-        if n > 0 && is_eof {
-            is_eof = false;
-            if line[n - 1] == b'\r' {
-                line.pop();
-                n -= 1;
+        $($async)* fn next_bytes_record(&mut self) -> $crate::Result<Option<Vec<Vec<u8>>>> {
+            let mut record = std::mem::take(&mut self.state.record);
+            let ret = self.read_byte_record(&mut record) $($await)*;
+            self.state.record = record;
+            if !ret? {
+                return Ok(None);
             }
+            Ok(Some(self.state.record.iter().map(<[u8]>::to_vec).collect()))
         }
-        self.num_line += 1;
-        self.offset += n;
-        if n >= 2 && line[n - 2] == b'\r' && line[n - 1] == b'\n' {
-            line[n - 2] = b'\n';
-            line.pop();
+
+        $($async)* fn read_record(
+            &mut self,
+            record: &mut $crate::record::ByteRecord,
+        ) -> $crate::Result<bool> {
+            loop {
+                match self.parse_record(record) $($await)* {
+                    Err(err) if err.is_recoverable() => {
+                        let pos = record.position().cloned().unwrap_or_default();
+                        self.state.handle_bad_record(pos, err)?;
+                    }
+                    ret => return ret,
+                }
+            }
         }
-        Ok(Slice { line, is_eof })
-    }
 
-    async fn read_slice(&mut self) -> Result<Slice> {
-        let mut s = Slice::default();
-        loop {
-            let n = self.r.read_until(b'\n', &mut s.line).await;
-            match n {
-                Err(e) => return Err(e.into()),
-                Ok(0) => {
-                    s.is_eof = true;
-                    return Ok(s);
+        $($async)* fn parse_record(
+            &mut self,
+            record: &mut $crate::record::ByteRecord,
+        ) -> $crate::Result<bool> {
+            loop {
+                self.fill_line() $($await)* ?;
+                match self.state.feed(record)? {
+                    $crate::core::Feed::Eof => return Ok(false),
+                    $crate::core::Feed::Record => return Ok(true),
+                    $crate::core::Feed::Skip | $crate::core::Feed::More => {}
                 }
-                Ok(_) => {
-                    if *s.line.last().unwrap() == b'\n' {
-                        return Ok(s);
+            }
+        }
+    };
+}
+
+pub(crate) use reader_api;
+
+// Streams over the records of the async readers, built on their `reader_api`.
+#[cfg(any(feature = "async", feature = "futures-io"))]
+macro_rules! reader_stream_api {
+    () => {
+        pub fn deserialize_stream<'a, T>(
+            &'a mut self,
+        ) -> impl ::futures_core::Stream<Item = $crate::Result<T>> + Send + Unpin + 'a
+        where
+            T: $crate::HeaderCSV + $crate::FromCSV + Send + 'a,
+            R: Send,
+        {
+            $crate::stream::try_unfold((self, None), |(rd, mut header)| async move {
+                if header.is_none() {
+                    match rd.header_for::<T>().await {
+                        Ok(h) => header = Some(h),
+                        Err(e) => return ((rd, header), Err(e)),
                     }
                 }
-            };
+                let ret = match rd.next_string_record().await {
+                    Ok(Some(record)) => {
+                        let record = $crate::reader::to_strings(&record);
+                        T::from_csv(header.as_ref().unwrap(), &record).map(Some)
+                    }
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                };
+                ((rd, header), ret)
+            })
         }
-    }
+
+        pub fn records(
+            &mut self,
+        ) -> impl ::futures_core::Stream<Item = $crate::Result<$crate::record::StringRecord>>
+        + Send
+        + Unpin
+        + '_
+        where
+            R: Send,
+        {
+            $crate::stream::try_unfold(self, |rd| async move {
+                let ret = rd.next_string_record().await;
+                (rd, ret)
+            })
+        }
+
+        pub fn into_stream<'a>(
+            self,
+        ) -> impl ::futures_core::Stream<Item = $crate::Result<$crate::record::StringRecord>>
+        + Send
+        + Unpin
+        + 'a
+        where
+            R: Send + 'a,
+        {
+            $crate::stream::try_unfold(self, |mut rd| async move {
+                let ret = rd.next_string_record().await;
+                (rd, ret)
+            })
+        }
+    };
 }
 
-fn length_nl(b: &[u8]) -> usize {
-    if b.len() > 0 && *b.last().unwrap() == b'\n' {
-        1
-    } else {
-        0
+#[cfg(feature = "futures-io")]
+pub(crate) use reader_stream_api;
+
+#[cfg(feature = "async")]
+pub struct Reader<R: AsyncRead + std::marker::Unpin> {
+    r: BufReader<R>,
+    state: ReaderState,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + std::marker::Unpin> Reader<R> {
+    pub fn new(r: R) -> Self {
+        Self {
+            r: BufReader::new(r),
+            state: ReaderState::new(),
+        }
+    }
+
+    reader_api!({async}, {.await});
+
+    reader_stream_api!();
+
+    async fn fill_line(&mut self) -> Result<()> {
+        self.state.line.clear();
+        let term = self.state.parser.term();
+        let limit = self.state.parser.line_limit();
+        (&mut self.r)
+            .take(limit)
+            .read_until(term, &mut self.state.line)
+            .await?;
+        Ok(())
     }
 }
 
-fn to_utf8(bytes: &[u8]) -> Result<String> {
-    Ok(from_utf8(bytes)?.to_string())
+#[cfg(feature = "async")]
+impl<R: AsyncRead + AsyncSeek + std::marker::Unpin> Reader<R> {
+    pub async fn seek(&mut self, pos: Position) -> Result<()> {
+        self.r.seek(SeekFrom::Start(pos.byte as u64)).await?;
+        self.state.seek_to(&pos);
+        Ok(())
+    }
 }
 
-fn to_encoding(bytes: &[u8], encoding: &'static Encoding) -> Result<String> {
-    let (str, _, _) = encoding.decode(bytes);
-    Ok(str.to_string())
+pub(crate) fn to_strings(record: &StringRecord) -> Vec<String> {
+    record.iter().map(String::from).collect()
 }
@@ -0,0 +1,321 @@
+use crate::err::{Error, Result};
+use encoding_rs::Encoding;
+use std::str::from_utf8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub(crate) byte: usize,
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+    pub(crate) record: usize,
+}
+
+impl Position {
+    pub fn new(byte: usize, line: usize, record: usize) -> Self {
+        Self {
+            byte,
+            line,
+            col: 1,
+            record,
+        }
+    }
+
+    /// Byte offset from the start of the input, starting at 0.
+    pub fn byte(&self) -> usize {
+        self.byte
+    }
+
+    /// Line number, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Byte column within the line, starting at 1.
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// Index of the record in the input (header included), starting at 0.
+    pub fn record(&self) -> usize {
+        self.record
+    }
+}
+
+// All fields live in one buffer, `ends[i]` is the end offset of field `i`.
+#[derive(Clone, Debug, Default)]
+pub struct ByteRecord {
+    buf: Vec<u8>,
+    ends: Vec<usize>,
+    position: Option<Position>,
+    positions: Vec<Position>,
+}
+
+impl ByteRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        let end = *self.ends.get(i)?;
+        let start = if i == 0 { 0 } else { self.ends[i - 1] };
+        Some(&self.buf[start..end])
+    }
+
+    pub fn iter(&self) -> ByteRecordIter<'_> {
+        ByteRecordIter { record: self, i: 0 }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_ref()
+    }
+
+    pub(crate) fn set_position(&mut self, position: Position) {
+        self.position = Some(position);
+    }
+
+    pub fn field_position(&self, i: usize) -> Option<&Position> {
+        self.positions.get(i)
+    }
+
+    pub fn push_field(&mut self, field: &[u8]) {
+        self.buf.extend_from_slice(field);
+        self.end_field(Position::default());
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.ends.clear();
+        self.position = None;
+        self.positions.clear();
+    }
+
+    pub(crate) fn extend_field(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn open_field_len(&self) -> usize {
+        self.buf.len() - self.ends.last().copied().unwrap_or(0)
+    }
+
+    pub(crate) fn push_byte(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    // Trims the field that has been extended but not ended yet.
+    pub(crate) fn trim_open_field(&mut self) {
+        let start = self.ends.last().copied().unwrap_or(0);
+        let field = &self.buf[start..];
+        let trimmed = trim_bytes(field);
+        let from = start + (trimmed.as_ptr() as usize - field.as_ptr() as usize);
+        let len = trimmed.len();
+        self.buf.copy_within(from..from + len, start);
+        self.buf.truncate(start + len);
+    }
+
+    pub(crate) fn end_field(&mut self, pos: Position) {
+        self.ends.push(self.buf.len());
+        self.positions.push(pos);
+    }
+
+    fn utf8_error(&self, field: usize, err: std::str::Utf8Error) -> Error {
+        Error::ErrUtf8 {
+            pos: self.position.clone(),
+            field,
+            err,
+        }
+    }
+}
+
+impl PartialEq for ByteRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for ByteRecord {}
+
+impl<T: AsRef<[u8]>> From<Vec<T>> for ByteRecord {
+    fn from(fields: Vec<T>) -> Self {
+        let mut record = ByteRecord::new();
+        for field in fields {
+            record.push_field(field.as_ref());
+        }
+        record
+    }
+}
+
+impl<'a> IntoIterator for &'a ByteRecord {
+    type Item = &'a [u8];
+    type IntoIter = ByteRecordIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct ByteRecordIter<'a> {
+    record: &'a ByteRecord,
+    i: usize,
+}
+
+impl<'a> Iterator for ByteRecordIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let field = self.record.get(self.i)?;
+        self.i += 1;
+        Some(field)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.record.len() - self.i;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for ByteRecordIter<'_> {}
+
+// A `ByteRecord` whose fields are each known to be valid UTF-8, which is what
+// makes the unchecked conversions below sound.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringRecord(ByteRecord);
+
+impl StringRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_byte_record(record: ByteRecord) -> Result<Self> {
+        for (i, field) in record.iter().enumerate() {
+            from_utf8(field).map_err(|err| record.utf8_error(i, err))?;
+        }
+        Ok(StringRecord(record))
+    }
+
+    pub(crate) fn decode_from(
+        &mut self,
+        record: &ByteRecord,
+        encoding: Option<&'static Encoding>,
+    ) -> Result<()> {
+        self.0.clear();
+        self.0.position = record.position.clone();
+        for (field, pos) in record.iter().zip(record.positions.iter()) {
+            match encoding {
+                None => self.0.extend_field(
+                    from_utf8(field)
+                        .map_err(|err| record.utf8_error(self.0.len(), err))?
+                        .as_bytes(),
+                ),
+                Some(encoding) => {
+                    let (str, _, _) = encoding.decode(field);
+                    self.0.extend_field(str.as_bytes());
+                }
+            }
+            self.0.end_field(pos.clone());
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&str> {
+        self.0
+            .get(i)
+            .map(|f| unsafe { std::str::from_utf8_unchecked(f) })
+    }
+
+    pub fn iter(&self) -> StringRecordIter<'_> {
+        StringRecordIter(self.0.iter())
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(self.0.as_slice()) }
+    }
+
+    pub fn position(&self) -> Option<&Position> {
+        self.0.position()
+    }
+
+    pub fn field_position(&self, i: usize) -> Option<&Position> {
+        self.0.field_position(i)
+    }
+
+    pub fn push_field(&mut self, field: &str) {
+        self.0.push_field(field.as_bytes());
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn as_byte_record(&self) -> &ByteRecord {
+        &self.0
+    }
+
+    pub fn into_byte_record(self) -> ByteRecord {
+        self.0
+    }
+}
+
+impl<T: AsRef<str>> From<Vec<T>> for StringRecord {
+    fn from(fields: Vec<T>) -> Self {
+        let mut record = StringRecord::new();
+        for field in fields {
+            record.push_field(field.as_ref());
+        }
+        record
+    }
+}
+
+impl<'a> IntoIterator for &'a StringRecord {
+    type Item = &'a str;
+    type IntoIter = StringRecordIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct StringRecordIter<'a>(ByteRecordIter<'a>);
+
+impl<'a> Iterator for StringRecordIter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|f| unsafe { std::str::from_utf8_unchecked(f) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for StringRecordIter<'_> {}
+
+// Trims Unicode whitespace when `field` is UTF-8, ASCII whitespace otherwise.
+pub(crate) fn trim_bytes(field: &[u8]) -> &[u8] {
+    match from_utf8(field) {
+        Ok(s) => s.trim().as_bytes(),
+        Err(_) => field.trim_ascii(),
+    }
+}
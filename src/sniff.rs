@@ -0,0 +1,198 @@
+use crate::{Terminator, dialect::Dialect, err::*, sync::Reader};
+use encoding_rs::{Encoding, GBK, UTF_8, WINDOWS_1252};
+use memchr::{memchr, memmem, memrchr, memrchr2};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+const QUOTES: [u8; 2] = [b'"', b'\''];
+const RECORD_SEPARATOR: u8 = 0x1e;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sniff {
+    pub dialect: Dialect,
+    /// The most common number of fields per record.
+    pub columns: usize,
+    /// How well the sample fits the dialect, from 0 to 1.
+    pub confidence: f64,
+    /// The bytes taken from the input, to be read again before the rest of it.
+    pub sample: Vec<u8>,
+}
+
+// Candidate delimiter and quote pair, scored by parsing the sample with it.
+struct Guess {
+    delimiter: u8,
+    quote: u8,
+    columns: usize,
+    score: f64,
+    records: Vec<Vec<String>>,
+}
+
+// Reads at most `max_bytes` from `r` and guesses the dialect of it.
+pub async fn sniff<R>(r: &mut R, max_bytes: usize) -> Result<Sniff>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut sample = Vec::with_capacity(max_bytes.min(64 * 1024));
+    let is_eof = r.take(max_bytes as u64).read_to_end(&mut sample).await? < max_bytes;
+
+    let (bom, bom_len) = match Encoding::for_bom(&sample) {
+        Some((encoding, len)) => (Some(encoding), len),
+        None => (None, 0),
+    };
+    let complete = if is_eof {
+        &sample[..]
+    } else {
+        cut_partial_line(&sample)
+    };
+    let encoding = bom.unwrap_or_else(|| guess_encoding(complete));
+    let (text, _) = encoding.decode_without_bom_handling(&complete[bom_len..]);
+    let terminator = guess_terminator(text.as_bytes());
+
+    // UTF-8 without a BOM needs no decoding at all.
+    let mut dialect = Dialect::rfc4180()
+        .with_terminator(terminator)
+        .with_encoding((encoding != UTF_8 || bom.is_some()).then_some(encoding));
+
+    let mut best: Option<Guess> = None;
+    for quote in QUOTES {
+        if quote != b'"' && !text.as_bytes().contains(&quote) {
+            continue;
+        }
+        for delimiter in DELIMITERS {
+            let guess = score(text.as_bytes(), delimiter, quote, terminator)?;
+            if best.as_ref().is_none_or(|b| better(&guess, b)) {
+                best = Some(guess);
+            }
+        }
+    }
+    let best = best.unwrap();
+    dialect.delimiter = vec![best.delimiter];
+    dialect.quote = best.quote;
+    dialect.header = has_header(&best.records, best.columns);
+    let mut confidence = best.score;
+    if best.columns <= 1 {
+        // no delimiter was found, a single column is only a fallback
+        confidence *= 0.5;
+    }
+    if best.records.len() < 2 {
+        confidence *= 0.5;
+    }
+    Ok(Sniff {
+        dialect,
+        columns: best.columns,
+        confidence,
+        sample,
+    })
+}
+
+// Prefers several columns, then the better score, then the earlier candidate.
+fn better(a: &Guess, b: &Guess) -> bool {
+    match ((a.columns > 1), (b.columns > 1)) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => a.score > b.score,
+    }
+}
+
+fn score(sample: &[u8], delimiter: u8, quote: u8, terminator: Terminator) -> Result<Guess> {
+    let dialect = Dialect::rfc4180()
+        .with_delimiter(&[delimiter])
+        .with_quote(quote)
+        .with_terminator(terminator)
+        .with_header(false);
+    let mut rd = Reader::new(sample)
+        .with_dialect(dialect)?
+        .with_allow_diff_field_num(true)
+        .with_error_policy(ErrorPolicy::Collect);
+    let records = rd.string_records().unwrap_or_default();
+    let bad = rd.bad_records().len();
+
+    let mut counts: Vec<(usize, usize)> = Vec::new();
+    for record in &records {
+        match counts.iter_mut().find(|(n, _)| *n == record.len()) {
+            Some((_, cnt)) => *cnt += 1,
+            None => counts.push((record.len(), 1)),
+        }
+    }
+    let (columns, cnt) = counts
+        .iter()
+        .copied()
+        .max_by_key(|&(n, cnt)| (cnt, n))
+        .unwrap_or((0, 0));
+    let total = records.len() + bad;
+    let score = if total == 0 {
+        0.0
+    } else {
+        cnt as f64 / total as f64
+    };
+    Ok(Guess {
+        delimiter,
+        quote,
+        columns,
+        score,
+        records,
+    })
+}
+
+// The sample likely ends in the middle of a record, drop it.
+fn cut_partial_line(sample: &[u8]) -> &[u8] {
+    match memrchr(b'\n', sample).or_else(|| memrchr2(b'\r', RECORD_SEPARATOR, sample)) {
+        Some(i) => &sample[..i + 1],
+        None => sample,
+    }
+}
+
+fn guess_encoding(sample: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(sample).is_ok() {
+        UTF_8
+    } else if GBK
+        .decode_without_bom_handling_and_without_replacement(sample)
+        .is_some()
+    {
+        // a lone Latin-1 byte is rarely followed by a valid GBK trail byte
+        GBK
+    } else {
+        WINDOWS_1252
+    }
+}
+
+fn guess_terminator(sample: &[u8]) -> Terminator {
+    if memmem::find(sample, b"\r\n").is_some() {
+        Terminator::CRLF
+    } else if memchr(b'\n', sample).is_some() {
+        Terminator::Any(b'\n')
+    } else if memchr(RECORD_SEPARATOR, sample).is_some() {
+        Terminator::Any(RECORD_SEPARATOR)
+    } else if memchr(b'\r', sample).is_some() {
+        Terminator::Any(b'\r')
+    } else {
+        Terminator::CRLF
+    }
+}
+
+// Like Python's `csv.Sniffer.has_header`: columns whose values below the first
+// row are all numbers, or all of one length, vote for a header when the first
+// row does not fit them and against it otherwise.
+fn has_header(records: &[Vec<String>], columns: usize) -> bool {
+    let Some((first, rest)) = records.split_first() else {
+        return false;
+    };
+    let rest: Vec<&Vec<String>> = rest.iter().filter(|r| r.len() == columns).collect();
+    if first.len() != columns || rest.is_empty() {
+        return false;
+    }
+    let is_number = |s: &str| s.trim().parse::<f64>().is_ok();
+    let mut votes = 0i32;
+    for col in 0..columns {
+        let head = first[col].as_str();
+        if rest.iter().all(|r| is_number(&r[col])) {
+            votes += if is_number(head) { -1 } else { 1 };
+        } else {
+            let len = rest[0][col].chars().count();
+            if len > 0 && rest.iter().all(|r| r[col].chars().count() == len) {
+                votes += if head.chars().count() == len { -1 } else { 1 };
+            }
+        }
+    }
+    votes > 0
+}
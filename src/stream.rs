@@ -0,0 +1,59 @@
+use crate::err::Result;
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+type StepFuture<'a, S, T> = Pin<Box<dyn Future<Output = (S, Result<Option<T>>)> + Send + 'a>>;
+
+/// Drives `step` over `state` until it yields `Ok(None)` or an error.
+/// The stream ends right after the first error is yielded.
+pub(crate) struct TryUnfold<'a, S, T, F> {
+    state: Option<S>,
+    step: F,
+    pending: Option<StepFuture<'a, S, T>>,
+}
+
+pub(crate) fn try_unfold<'a, S, T, F, Fut>(state: S, step: F) -> TryUnfold<'a, S, T, F>
+where
+    F: FnMut(S) -> Fut,
+    Fut: Future<Output = (S, Result<Option<T>>)> + Send + 'a,
+{
+    TryUnfold {
+        state: Some(state),
+        step,
+        pending: None,
+    }
+}
+
+impl<'a, S, T, F, Fut> Stream for TryUnfold<'a, S, T, F>
+where
+    S: Unpin,
+    F: FnMut(S) -> Fut + Unpin,
+    Fut: Future<Output = (S, Result<Option<T>>)> + Send + 'a,
+{
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.pending.is_none() {
+            match this.state.take() {
+                None => return Poll::Ready(None),
+                Some(state) => this.pending = Some(Box::pin((this.step)(state))),
+            }
+        }
+        let (state, item) = match this.pending.as_mut().unwrap().as_mut().poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(ret) => ret,
+        };
+        this.pending = None;
+        match item {
+            Ok(None) => Poll::Ready(None),
+            Ok(Some(v)) => {
+                this.state = Some(state);
+                Poll::Ready(Some(Ok(v)))
+            }
+            Err(e) => Poll::Ready(Some(Err(e))),
+        }
+    }
+}
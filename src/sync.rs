@@ -0,0 +1,99 @@
+use crate::err::*;
+use crate::reader::{ReaderState, reader_api};
+use crate::record::{Position, StringRecord};
+use crate::writer::{WriterState, writer_api};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+pub struct Reader<R: Read> {
+    r: BufReader<R>,
+    state: ReaderState,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(r: R) -> Self {
+        Self {
+            r: BufReader::new(r),
+            state: ReaderState::new(),
+        }
+    }
+
+    reader_api!({}, {});
+
+    // Stops after the first error.
+    pub fn records(&mut self) -> impl Iterator<Item = Result<StringRecord>> + '_ {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let ret = self.next_string_record().transpose();
+            done = !matches!(ret, Some(Ok(_)));
+            ret
+        })
+    }
+
+    fn fill_line(&mut self) -> Result<()> {
+        self.state.line.clear();
+        let term = self.state.parser.term();
+        let limit = self.state.parser.line_limit();
+        (&mut self.r)
+            .take(limit)
+            .read_until(term, &mut self.state.line)?;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    pub fn seek(&mut self, pos: Position) -> Result<()> {
+        self.r.seek(SeekFrom::Start(pos.byte as u64))?;
+        self.state.seek_to(&pos);
+        Ok(())
+    }
+}
+
+/// Records are buffered. Like `std::io::BufWriter`, whatever is left is
+/// written when the writer is dropped, ignoring errors; call `flush` or
+/// `finish` to see them.
+pub struct Writer<W: Write> {
+    // only taken by `finish`
+    w: Option<W>,
+    state: WriterState,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(w: W) -> Self {
+        Writer {
+            w: Some(w),
+            state: WriterState::new(),
+        }
+    }
+
+    writer_api!({}, {});
+
+    fn write_buf(&mut self) -> Result<()> {
+        if let Some(w) = &mut self.w {
+            w.write_all(&self.state.buf)?;
+        }
+        self.state.buf.clear();
+        Ok(())
+    }
+
+    fn flush_sink(&mut self) -> Result<()> {
+        if let Some(w) = &mut self.w {
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    fn into_sink(mut self) -> W {
+        self.w.take().expect("the sink is only taken once")
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if !self.state.buf.is_empty() {
+            let _ = self.write_buf();
+        }
+    }
+}
@@ -1,158 +1,319 @@
-use std::fmt::Display;
-
-use crate::{HeaderCSV, ToCSV, err::*};
-use anyhow::Result;
+use crate::core::Quoter;
 use encoding_rs::Encoding;
-use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
-
-pub struct Writer<R> {
-    w: BufWriter<R>,
-    comma: u8,
-    write_header: bool,
-    custom_header: Option<Vec<String>>,
-    use_crlf: bool,
-    encoding: Option<&'static Encoding>,
+use std::borrow::Cow;
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Always,
+    #[default]
+    Necessary,
+    NonNumeric,
+    /// Fields are written as is, special bytes are prefixed with the escape
+    /// byte if there is one. A reader with the same escape reads them back.
+    Never,
 }
 
-impl<R: AsyncWrite + std::marker::Unpin> Writer<R> {
-    pub fn new(w: R) -> Self {
-        Writer {
-            w: BufWriter::new(w),
-            comma: b',',
+// Records are collected here and handed to the sink once this much is buffered.
+pub(crate) const BUF_SIZE: usize = 8 * 1024;
+
+// Everything a writer needs apart from its output.
+pub(crate) struct WriterState {
+    pub(crate) quoter: Quoter,
+    pub(crate) write_header: bool,
+    pub(crate) custom_header: Option<Vec<String>>,
+    pub(crate) encoding: Option<&'static Encoding>,
+    pub(crate) buf: Vec<u8>,
+    started: bool,
+}
+
+impl WriterState {
+    pub(crate) fn new() -> Self {
+        WriterState {
+            quoter: Quoter::new(),
             write_header: false,
             custom_header: None,
-            use_crlf: false,
             encoding: None,
+            buf: Vec::with_capacity(BUF_SIZE),
+            started: false,
         }
     }
 
-    pub fn with_comma(mut self, comma: u8) -> Result<Self> {
-        match comma {
-            b'\n' | b'\r' | b'"' => Err(ErrorKind::ErrInvalidDelim.into()),
-            _ => {
-                self.comma = comma;
-                Ok(self)
-            }
+    // Writes the header ahead of the first record, once per writer. The
+    // settings are checked together here, the builders may come in any order.
+    pub(crate) fn start(&mut self) -> crate::Result<()> {
+        if !self.quoter.double_quote && self.quoter.escape.is_none() {
+            return Err(crate::Error::ErrInvalidEscape);
+        }
+        if self.started {
+            return Ok(());
         }
+        self.started = true;
+        if self.write_header
+            && let Some(header) = self.custom_header.take()
+        {
+            let ret = self.write_record(&header);
+            self.custom_header = Some(header);
+            ret?;
+        }
+        Ok(())
     }
 
-    pub fn with_write_header(mut self, write_header: bool) -> Self {
-        self.write_header = write_header;
-        self
+    // Fields that are not UTF-8 can not be transcoded and are written as is.
+    pub(crate) fn write_record<I, F>(&mut self, record: I) -> crate::Result<()>
+    where
+        I: IntoIterator<Item = F>,
+        F: AsRef<[u8]>,
+    {
+        let fields: Vec<F> = record.into_iter().collect();
+        let fields: Vec<Cow<[u8]>> = fields
+            .iter()
+            .map(|field| {
+                let field = field.as_ref();
+                match (self.encoding, std::str::from_utf8(field)) {
+                    (Some(encoding), Ok(s)) => encoding.encode(s).0,
+                    _ => Cow::Borrowed(field),
+                }
+            })
+            .collect();
+        self.quoter
+            .write_record(fields.iter().map(|f| f.as_ref()), &mut self.buf)
     }
+}
 
-    pub fn with_custom_header(mut self, custom_header: Vec<String>) -> Self {
-        self.custom_header = Some(custom_header);
-        self
-    }
+// The writer API shared by the blocking and async writers. `$async` and
+// `$await` are either empty or `async` and `.await`, the writer provides
+// `write_buf` to hand `self.state.buf` to its sink, `flush_sink` and
+// `into_sink`.
+macro_rules! writer_api {
+    ({$($async:tt)*}, {$($await:tt)*}) => {
+        pub fn with_comma(self, comma: u8) -> $crate::Result<Self> {
+            self.with_delimiter(&[comma])
+        }
 
-    pub fn with_use_crlf(mut self, use_crlf: bool) -> Self {
-        self.use_crlf = use_crlf;
-        self
-    }
+        pub fn with_delimiter(mut self, delimiter: &[u8]) -> $crate::Result<Self> {
+            let q = &self.state.quoter;
+            let invalid = |b: &u8| q.is_line_break(*b) || *b == q.quote || Some(*b) == q.escape;
+            if delimiter.is_empty() || delimiter.iter().any(invalid) {
+                return Err($crate::Error::ErrInvalidDelim);
+            }
+            self.state.quoter.comma = delimiter.to_vec();
+            Ok(self)
+        }
 
-    pub fn with_encoding(mut self, encoding: &'static Encoding) -> Self {
-        self.encoding = Some(encoding);
-        self
-    }
+        pub fn with_quote(mut self, quote: u8) -> $crate::Result<Self> {
+            let q = &mut self.state.quoter;
+            match quote {
+                _ if q.is_line_break(quote) => Err($crate::Error::ErrInvalidQuote),
+                _ if q.comma.contains(&quote) || q.escape == Some(quote) => {
+                    Err($crate::Error::ErrInvalidQuote)
+                }
+                _ => {
+                    q.quote = quote;
+                    Ok(self)
+                }
+            }
+        }
 
-    pub async fn serialize<T>(&mut self, records: &Vec<T>) -> Result<()>
-    where
-        T: HeaderCSV + ToCSV,
-    {
-        if self.write_header && self.custom_header.is_none() {
-            self.custom_header = Some(T::get_header());
+        pub fn with_escape(mut self, escape: Option<u8>) -> $crate::Result<Self> {
+            let q = &mut self.state.quoter;
+            match escape {
+                Some(e) if q.is_line_break(e) => Err($crate::Error::ErrInvalidEscape),
+                Some(e) if q.comma.contains(&e) || e == q.quote => {
+                    Err($crate::Error::ErrInvalidEscape)
+                }
+                _ => {
+                    q.escape = escape;
+                    Ok(self)
+                }
+            }
         }
-        self.write_records(records.iter().map(|v| v.to_csv()).collect())
-            .await?;
-        Ok(())
-    }
 
-    pub async fn write_records<T>(&mut self, records: Vec<Vec<T>>) -> Result<()>
-    where
-        T: Display,
-    {
-        if self.write_header && self.custom_header.is_some() {
-            self.write_record(self.custom_header.clone().unwrap())
-                .await?;
+        /// Without doubling, quotes inside a quoted field need an escape.
+        /// Writing fails with `ErrInvalidEscape` if `false` is set without one.
+        pub fn with_double_quote(mut self, double_quote: bool) -> Self {
+            self.state.quoter.double_quote = double_quote;
+            self
         }
-        for record in records {
-            let _ = self
-                .write_record(record.iter().map(|f| f.to_string()).collect())
-                .await?;
+
+        pub fn with_write_header(mut self, write_header: bool) -> Self {
+            self.state.write_header = write_header;
+            self
         }
-        let _ = self.w.flush().await?;
-        Ok(())
-    }
 
-    async fn write_record(&mut self, record: Vec<String>) -> Result<()> {
-        let comma = &[self.comma];
-        for (n, field) in record.iter().enumerate() {
-            let encoded_field;
-            let mut field = match self.encoding {
-                None => field.as_bytes(),
-                Some(encoding) => {
-                    let (ret, _, _) = encoding.encode(field);
-                    encoded_field = ret;
-                    encoded_field.as_ref()
-                }
+        pub fn with_custom_header(mut self, custom_header: Vec<String>) -> Self {
+            self.state.custom_header = Some(custom_header);
+            self
+        }
+
+        pub fn with_quote_style(mut self, style: $crate::writer::QuoteStyle) -> Self {
+            self.state.quoter.style = style;
+            self
+        }
+
+        pub fn with_use_crlf(mut self, use_crlf: bool) -> Self {
+            self.state.quoter.terminator = if use_crlf {
+                $crate::Terminator::CRLF
+            } else {
+                $crate::Terminator::Any(b'\n')
             };
-            if n > 0 {
-                let _ = self.w.write(comma).await?;
+            self
+        }
+
+        pub fn with_terminator(mut self, terminator: $crate::Terminator) -> $crate::Result<Self> {
+            let q = &mut self.state.quoter;
+            if let $crate::Terminator::Any(t) = terminator
+                && (q.comma.contains(&t) || t == q.quote || q.escape == Some(t))
+            {
+                return Err($crate::Error::ErrInvalidTerminator);
             }
-            if !self.field_needs_quotes(field) {
-                let _ = self.w.write(field).await?;
-                continue;
+            q.terminator = terminator;
+            Ok(self)
+        }
+
+        pub fn with_encoding(mut self, encoding: &'static ::encoding_rs::Encoding) -> Self {
+            self.state.encoding = Some(encoding);
+            self
+        }
+
+        pub fn with_dialect(mut self, dialect: $crate::dialect::Dialect) -> $crate::Result<Self> {
+            dialect.validate()?;
+            let q = &mut self.state.quoter;
+            q.comma = dialect.delimiter;
+            q.quote = dialect.quote;
+            q.escape = dialect.escape;
+            q.double_quote = dialect.double_quote;
+            q.terminator = dialect.terminator;
+            q.comment = dialect.comment;
+            self.state.write_header = dialect.header;
+            self.state.encoding = dialect.encoding;
+            Ok(self)
+        }
+
+        /// Writes `records` and flushes, see `write_records`.
+        pub $($async)* fn serialize<T>(&mut self, records: &[T]) -> $crate::Result<()>
+        where
+            T: $crate::HeaderCSV + $crate::ToCSV,
+        {
+            if self.state.write_header && self.state.custom_header.is_none() {
+                self.state.custom_header = Some(T::get_header());
             }
-            let mut cnt = 0;
-            let _ = self.w.write(b"\"").await?;
-            while field.len() > 0 {
-                cnt += 1;
-                if cnt > 5 {
-                    break;
-                }
-                let i = field
-                    .iter()
-                    .position(|&c| c == b'"' || c == b'\r' || c == b'\n');
-                let i = i.unwrap_or(field.len());
-                let _ = self.w.write(&field[..i]).await?;
-                field = &field[i..];
-                if field.len() > 0 {
-                    match field[0] {
-                        b'"' => {
-                            let _ = self.w.write(b"\"\"").await?;
-                        }
-                        b'\r' => {
-                            if !self.use_crlf {
-                                let _ = self.w.write(b"\r").await?;
-                            }
-                        }
-                        b'\n' => {
-                            let _ = self
-                                .w
-                                .write(if self.use_crlf { b"\r\n" } else { b"\n" })
-                                .await?;
-                        }
-                        _ => {}
-                    };
-                    field = &field[1..]
-                }
+            self.write_records(records.iter().map(|v| v.to_csv()))
+                $($await)* ?;
+            Ok(())
+        }
+
+        /// Buffers one record, call `flush` or `finish` when done.
+        pub $($async)* fn serialize_one<T>(&mut self, record: &T) -> $crate::Result<()>
+        where
+            T: $crate::HeaderCSV + $crate::ToCSV,
+        {
+            if self.state.write_header && self.state.custom_header.is_none() {
+                self.state.custom_header = Some(T::get_header());
             }
-            let _ = self.w.write(b"\"").await?;
+            self.write_record(record.to_csv()) $($await)* ?;
+            Ok(())
+        }
+
+        /// Buffers one record, the header is taken from the field names of the
+        /// first one unless a custom header is set. Named fields are written
+        /// in the order of the header, a column without a field is an error.
+        #[cfg(feature = "serde")]
+        pub $($async)* fn serialize_serde<T>(&mut self, record: &T) -> $crate::Result<()>
+        where
+            T: ::serde::Serialize + ?Sized,
+        {
+            let (header, mut fields) = $crate::serializer::to_record(record)?;
+            if self.state.write_header && self.state.custom_header.is_none() && !header.is_empty() {
+                self.state.custom_header = Some(header.clone());
+            }
+            if let Some(columns) = &self.state.custom_header
+                && !header.is_empty()
+            {
+                fields = $crate::serializer::reorder(&header, fields, columns)?;
+            }
+            self.write_record(fields) $($await)* ?;
+            Ok(())
+        }
+
+        /// Writes `records` and flushes. The header goes ahead of the first
+        /// record the writer writes, it is not repeated for later batches.
+        pub $($async)* fn write_records<I, R, T>(&mut self, records: I) -> $crate::Result<()>
+        where
+            I: IntoIterator<Item = R>,
+            R: IntoIterator<Item = T>,
+            T: ::std::fmt::Display,
+        {
+            self.state.start()?;
+            for record in records {
+                self.write_record(record.into_iter().map(|f| f.to_string()))
+                    $($await)* ?;
+            }
+            self.flush() $($await)* ?;
+            Ok(())
         }
-        let _ = self
-            .w
-            .write(if self.use_crlf { b"\r\n" } else { b"\n" })
-            .await?;
-        Ok(())
-    }
 
-    fn field_needs_quotes(&self, field: &[u8]) -> bool {
-        for &b in field {
-            if b == b'\n' || b == b'\r' || b == b'"' || b == self.comma {
-                return true;
+        /// Buffers one record, call `flush` or `finish` when done.
+        pub $($async)* fn write_record<I, F>(&mut self, record: I) -> $crate::Result<()>
+        where
+            I: IntoIterator<Item = F>,
+            F: AsRef<[u8]>,
+        {
+            self.state.start()?;
+            self.state.write_record(record)?;
+            if self.state.buf.len() >= $crate::writer::BUF_SIZE {
+                self.write_buf() $($await)* ?;
             }
+            Ok(())
         }
-        return false;
+
+        pub $($async)* fn flush(&mut self) -> $crate::Result<()> {
+            self.write_buf() $($await)* ?;
+            self.flush_sink() $($await)* ?;
+            Ok(())
+        }
+
+        /// Flushes and returns the underlying writer.
+        pub $($async)* fn finish(mut self) -> $crate::Result<W> {
+            self.flush() $($await)* ?;
+            Ok(self.into_sink())
+        }
+    };
+}
+
+pub(crate) use writer_api;
+
+#[cfg(feature = "async")]
+pub struct Writer<W> {
+    w: W,
+    state: WriterState,
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite + std::marker::Unpin> Writer<W> {
+    pub fn new(w: W) -> Self {
+        Writer {
+            w,
+            state: WriterState::new(),
+        }
+    }
+
+    writer_api!({async}, {.await});
+
+    async fn write_buf(&mut self) -> crate::Result<()> {
+        self.w.write_all(&self.state.buf).await?;
+        self.state.buf.clear();
+        Ok(())
+    }
+
+    async fn flush_sink(&mut self) -> crate::Result<()> {
+        self.w.flush().await?;
+        Ok(())
+    }
+
+    fn into_sink(self) -> W {
+        self.w
     }
 }
@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod core_test {
+
+    use csv::Error;
+    use csv::core::{Parsed, Parser};
+    use csv::dialect::Dialect;
+    use csv::err::Limit;
+    use csv::reader::Trim;
+    use csv::record::ByteRecord;
+    use csv::sync::Reader;
+
+    fn parse_chunks(parser: &mut Parser, chunks: &[&[u8]]) -> Vec<Vec<Vec<u8>>> {
+        let mut records = Vec::new();
+        let mut record = ByteRecord::new();
+        for chunk in chunks {
+            parser.feed(chunk);
+            while parser.read_record(&mut record).unwrap() == Parsed::Record {
+                records.push(record.iter().map(<[u8]>::to_vec).collect());
+            }
+        }
+        parser.finish();
+        while parser.read_record(&mut record).unwrap() == Parsed::Record {
+            records.push(record.iter().map(<[u8]>::to_vec).collect());
+        }
+        records
+    }
+
+    #[test]
+    fn test_simple() {
+        let mut parser = Parser::new();
+        let mut record = ByteRecord::new();
+        parser.feed(b"a,b\nc,");
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::Record);
+        assert_eq!(record, ByteRecord::from(vec!["a", "b"]));
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::NeedInput);
+        parser.feed(b"d");
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::NeedInput);
+        parser.finish();
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::Record);
+        assert_eq!(record, ByteRecord::from(vec!["c", "d"]));
+        assert_eq!(record.position().unwrap().byte(), 4);
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::End);
+    }
+
+    #[test]
+    fn test_every_split() {
+        let data = b"h1,h2\r\n\"a\r\n\"\"b\"\"\",c\r\n\r\n# x\r\nd,\"e,f\"\r\n";
+        let expect = Reader::new(&data[..])
+            .with_comment(b'#')
+            .unwrap()
+            .bytes_records()
+            .unwrap();
+        assert_eq!(expect.len(), 3);
+        for i in 0..data.len() {
+            for j in i..data.len() {
+                let mut parser = Parser::new()
+                    .with_dialect(Dialect::rfc4180().with_comment(Some(b'#')))
+                    .unwrap();
+                let chunks = [&data[..i], &data[i..j], &data[j..]];
+                assert_eq!(parse_chunks(&mut parser, &chunks), expect, "{i} {j}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_quoted_across_chunks() {
+        let mut parser = Parser::new();
+        let records = parse_chunks(&mut parser, &[b"x,\"long", b"\nfield", b"\"\n"]);
+        assert_eq!(records, vec![vec![b"x".to_vec(), b"long\nfield".to_vec()]]);
+    }
+
+    #[test]
+    fn test_dialect() {
+        let dialect = Dialect::unix()
+            .with_delimiter(b"|")
+            .with_terminator(csv::Terminator::Any(0x1e))
+            .with_trim(Trim::All);
+        let mut parser = Parser::new().with_dialect(dialect).unwrap();
+        let records = parse_chunks(&mut parser, &[b" a | b\x1e", b"c|d"]);
+        assert_eq!(records, vec![vec![b"a".to_vec(), b"b".to_vec()], vec![
+            b"c".to_vec(),
+            b"d".to_vec()
+        ]]);
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let mut parser = Parser::new();
+        let mut record = ByteRecord::new();
+        parser.feed(b"a,b\nc\nd,\"e\n");
+        parser.finish();
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::Record);
+        let err = parser.read_record(&mut record).unwrap_err();
+        assert!(matches!(err, Error::ErrFieldNum(2, _, 2, 1)));
+        let err = parser.read_record(&mut record).unwrap_err();
+        assert!(matches!(err, Error::ErrQuote(3, _)));
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::End);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut parser = Parser::new().with_max_record_bytes(8);
+        let mut record = ByteRecord::new();
+        parser.feed(b"a,b\nccc");
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::Record);
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::NeedInput);
+        parser.feed(b"cccccc");
+        let err = parser.read_record(&mut record).unwrap_err();
+        assert!(matches!(err, Error::ErrLimit {
+            limit: Limit::RecordBytes,
+            max: 8,
+            ..
+        }));
+
+        // the bad record is dropped up to its terminator, not kept in memory
+        let mut parser = Parser::new().with_max_record_bytes(5);
+        parser.feed(b"a,b\ncccccccccc");
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::Record);
+        let err = parser.read_record(&mut record).unwrap_err();
+        assert!(matches!(err, Error::ErrLimit {
+            limit: Limit::RecordBytes,
+            max: 5,
+            ..
+        }));
+        assert_eq!(parser.buffered(), 0);
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::NeedInput);
+        parser.feed(b"cccccccccc");
+        assert_eq!(parser.buffered(), 0);
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::NeedInput);
+        parser.feed(b"cc\nd,e\n");
+        assert_eq!(parser.buffered(), 4);
+        assert_eq!(parser.position().byte(), 27);
+        assert_eq!(parser.position().line(), 3);
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::Record);
+        assert_eq!(record, ByteRecord::from(vec!["d", "e"]));
+        parser.finish();
+        assert_eq!(parser.read_record(&mut record).unwrap(), Parsed::End);
+
+        let mut parser = Parser::new().with_max_fields(1);
+        parser.feed(b"a,b\n");
+        let err = parser.read_record(&mut record).unwrap_err();
+        assert!(matches!(err, Error::ErrLimit {
+            limit: Limit::Fields,
+            ..
+        }));
+    }
+}
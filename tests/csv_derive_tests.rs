@@ -1,11 +1,12 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "async"))]
 mod csv_derive_test {
     use std::fmt::Display;
     use std::str::FromStr;
 
-    use anyhow::Result;
-    use csv::{FromCSV, HeaderCSV, ToCSV, err::ErrorKind};
+    use csv::reader::{Reader, Trim};
+    use csv::{Error, FromCSV, HeaderCSV, ToCSV};
     use macros::{CSVFrom, CSVHeader, CSVTo};
+    use tokio_stream::StreamExt;
 
     #[test]
     #[allow(unused)]
@@ -19,11 +20,13 @@ mod csv_derive_test {
             symbol: "ZVZZT".to_string(),
             price: 1.23,
         };
-        let tk_from = Tick::from_csv(&Vec::new(), &Vec::new()).unwrap();
+        let header = vec!["symbol".to_string(), "price".to_string()];
+        let record = vec!["ZVZZT".to_string(), (1.23).to_string()];
+        let tk_from = Tick::from_csv(&header, &record).unwrap();
 
-        assert_eq!(Tick::get_header(), Vec::<String>::new());
-        assert_eq!(tk_from, Tick::default());
-        assert_eq!(tk.to_csv(), Vec::<String>::new());
+        assert_eq!(Tick::get_header(), header);
+        assert_eq!(tk_from, tk);
+        assert_eq!(tk.to_csv(), record);
     }
 
     #[test]
@@ -31,7 +34,7 @@ mod csv_derive_test {
     fn test_one_field() {
         #[derive(CSVHeader, CSVFrom, CSVTo, Default, PartialEq, Debug)]
         struct Tick {
-            #[csv(field = "symbol")]
+            #[csv(field = "sym")]
             symbol: String,
             price: f64,
         }
@@ -39,18 +42,63 @@ mod csv_derive_test {
             symbol: "ZVZZT".to_string(),
             price: 1.23,
         };
-        let header = vec!["symbol".to_string()];
-        let record = vec!["ZVZZT".to_string()];
+        let header = vec!["sym".to_string(), "price".to_string()];
+        let record = vec!["ZVZZT".to_string(), (1.23).to_string()];
         let tk_from = Tick::from_csv(&header, &record).unwrap();
 
         assert_eq!(Tick::get_header(), header);
-        assert_eq!(tk_from, Tick {
-            symbol: "ZVZZT".to_string(),
-            price: f64::default(),
-        });
+        assert_eq!(tk_from, tk);
         assert_eq!(tk.to_csv(), record);
     }
 
+    #[test]
+    fn test_skip() {
+        #[derive(CSVHeader, CSVFrom, CSVTo, Default, PartialEq, Debug)]
+        struct Tick {
+            r#type: String,
+            #[csv(skip)]
+            cache: u32,
+            #[csv(skip_serializing)]
+            venue: String,
+            #[csv(field = "px", skip_deserializing)]
+            price: f64,
+        }
+        let tk = Tick {
+            r#type: "T".to_string(),
+            cache: 1,
+            venue: "X".to_string(),
+            price: 1.5,
+        };
+        assert_eq!(Tick::get_header(), vec!["type", "px"]);
+        assert_eq!(tk.to_csv(), vec!["T", "1.5"]);
+
+        let header = vec!["px".to_string(), "venue".to_string(), "type".to_string()];
+        let record = vec!["2".to_string(), "Y".to_string(), "U".to_string()];
+        assert_eq!(Tick::from_csv(&header, &record).unwrap(), Tick {
+            r#type: "U".to_string(),
+            cache: 0,
+            venue: "Y".to_string(),
+            price: 0.0,
+        });
+    }
+
+    #[tokio::test]
+    async fn test_skip_no_header() {
+        #[derive(CSVHeader, CSVFrom, Default, PartialEq, Debug)]
+        struct Row {
+            a: u32,
+            #[csv(skip_serializing)]
+            b: String,
+        }
+        // without a header row the columns come from `get_header`
+        let mut rd = Reader::new("1,zz\n".as_bytes());
+        let rows: Vec<Row> = rd.deserialize().await.unwrap();
+        assert_eq!(rows, vec![Row {
+            a: 1,
+            b: String::new()
+        }]);
+    }
+
     #[test]
     #[allow(unused)]
     fn test_one_miss() {
@@ -67,11 +115,11 @@ mod csv_derive_test {
         let header = vec![];
         let record = vec!["ZVZZT".to_string()];
         let res = Tick::from_csv(&header, &record);
-        assert_eq!(res.is_err(), true);
-        assert_eq!(
-            *res.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrMissField("symbol".to_string())
-        );
+        assert!(res.is_err());
+        assert!(matches!(
+            res.err().unwrap(),
+            Error::ErrMissField(f) if f == "symbol"
+        ));
     }
 
     #[test]
@@ -207,15 +255,167 @@ mod csv_derive_test {
             symbol: "ZVZZT".to_string(),
             price: 1.23,
         };
-        let header = vec!["symbol".to_string()];
-        let record = vec!["ZVZZT".to_string()];
+        let header = vec!["symbol".to_string(), "price".to_string()];
+        let record = vec!["ZVZZT".to_string(), (1.23).to_string()];
         let tk_from = Tick::from_csv(&header, &record).unwrap();
 
         assert_eq!(Tick::<String>::get_header(), header);
-        assert_eq!(tk_from, Tick {
+        assert_eq!(tk_from, tk);
+        assert_eq!(tk.to_csv(), record);
+    }
+
+    #[test]
+    fn test_option() {
+        #[derive(CSVHeader, CSVFrom, CSVTo, Default, PartialEq, Debug)]
+        struct Tick {
+            symbol: String,
+            price: Option<f64>,
+            size: std::option::Option<u32>,
+        }
+        let header = vec![
+            "symbol".to_string(),
+            "price".to_string(),
+            "size".to_string(),
+        ];
+        let record = vec!["ZVZZT".to_string(), String::new(), "3".to_string()];
+        let tk = Tick::from_csv(&header, &record).unwrap();
+        assert_eq!(tk, Tick {
             symbol: "ZVZZT".to_string(),
-            price: f64::default(),
+            price: None,
+            size: Some(3),
         });
         assert_eq!(tk.to_csv(), record);
+
+        let record = vec!["ZVZZT".to_string(), "NA".to_string(), String::new()];
+        let err = Tick::from_csv(&header, &record).err().unwrap();
+        assert!(matches!(err, Error::ErrParse { ty: "f64", .. }));
+    }
+
+    #[test]
+    fn test_null_sentinel() {
+        #[derive(CSVHeader, CSVFrom, CSVTo, Default, PartialEq, Debug)]
+        #[csv(null = "NA", null = "NULL", write_null = "NA")]
+        struct Tick {
+            price: Option<f64>,
+            #[csv(field = "qty", null = "\\N", write_null = "\\N")]
+            size: Option<u32>,
+            venue: Option<String>,
+        }
+        let header = vec!["price".to_string(), "qty".to_string(), "venue".to_string()];
+        let record = vec!["NULL".to_string(), "\\N".to_string(), "NA".to_string()];
+        let tk = Tick::from_csv(&header, &record).unwrap();
+        assert_eq!(tk, Tick::default());
+        assert_eq!(tk.to_csv(), vec!["NA", "\\N", "NA"]);
+
+        // sentinels set on a field replace those of the struct
+        let record = vec!["NA".to_string(), "NA".to_string(), "x".to_string()];
+        let err = Tick::from_csv(&header, &record).err().unwrap();
+        assert!(matches!(err, Error::ErrParse { ref field, .. } if field == "qty"));
+    }
+
+    #[tokio::test]
+    async fn test_null_sentinel_escaped() {
+        #[derive(CSVHeader, CSVFrom, Default, PartialEq, Debug)]
+        struct Row {
+            id: u32,
+            #[csv(null = "\\N")]
+            qty: Option<u32>,
+        }
+        // MySQL `SELECT ... INTO OUTFILE` writes NULL as `\N`
+        let data = "id,qty\n1,\\N\n2,3\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_has_header(true)
+            .with_escape(Some(b'\\'))
+            .unwrap();
+        let rows: Vec<Row> = rd.deserialize().await.unwrap();
+        assert_eq!(rows, vec![Row { id: 1, qty: None }, Row {
+            id: 2,
+            qty: Some(3)
+        }]);
+    }
+
+    #[tokio::test]
+    async fn test_deserialize_stream() {
+        #[derive(CSVHeader, CSVFrom, Default, PartialEq, Debug)]
+        struct Tick {
+            #[csv(field = "symbol")]
+            symbol: String,
+            #[csv(field = "price")]
+            price: f64,
+        }
+        let data = "ZVZZT,1.23\nZXZZT,4.56\nZWZZT,x\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let mut ticks = rd.deserialize_stream::<Tick>();
+        assert_eq!(ticks.next().await.unwrap().unwrap(), Tick {
+            symbol: "ZVZZT".to_string(),
+            price: 1.23,
+        });
+        assert_eq!(ticks.next().await.unwrap().unwrap(), Tick {
+            symbol: "ZXZZT".to_string(),
+            price: 4.56,
+        });
+        assert!(ticks.next().await.unwrap().is_err());
+        assert!(ticks.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_deserialize_file_header() {
+        #[derive(CSVHeader, CSVFrom, Default, PartialEq, Debug)]
+        struct Tick {
+            #[csv(field = "symbol")]
+            symbol: String,
+            #[csv(field = "price")]
+            price: f64,
+        }
+        let data = "price,venue,symbol\n1.23,X,ZVZZT\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        let ticks = rd.deserialize::<Tick>().await.unwrap();
+        assert_eq!(ticks, vec![Tick {
+            symbol: "ZVZZT".to_string(),
+            price: 1.23,
+        }]);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        #[derive(CSVHeader, CSVFrom, Default, PartialEq, Debug)]
+        struct Tick {
+            #[csv(field = "price")]
+            price: f64,
+        }
+        let header = vec!["price".to_string()];
+        let record = vec!["x".to_string()];
+        let err = Tick::from_csv(&header, &record).err().unwrap();
+        match &err {
+            Error::ErrParse {
+                field, value, ty, ..
+            } => {
+                assert_eq!(field, "price");
+                assert_eq!(value, "x");
+                assert_eq!(*ty, "f64");
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_deserialize_trim() {
+        #[derive(CSVHeader, CSVFrom, Default, PartialEq, Debug)]
+        struct Tick {
+            #[csv(field = "symbol")]
+            symbol: String,
+            #[csv(field = "price")]
+            price: f64,
+        }
+        let data = "symbol , price\n ZVZZT , 1.23\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_has_header(true)
+            .with_trim(Trim::All);
+        let ticks = rd.deserialize::<Tick>().await.unwrap();
+        assert_eq!(ticks, vec![Tick {
+            symbol: "ZVZZT".to_string(),
+            price: 1.23,
+        }]);
     }
 }
@@ -0,0 +1,100 @@
+#[cfg(all(test, feature = "futures-io"))]
+mod futures_test {
+
+    use csv::Error;
+    use csv::dialect::Dialect;
+    use csv::futures::{Reader, Writer};
+    use csv::record::{ByteRecord, StringRecord};
+    use futures::StreamExt;
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use macros::{CSVFrom, CSVHeader, CSVTo};
+
+    #[derive(CSVHeader, CSVFrom, CSVTo, Debug, Default, PartialEq)]
+    struct Row {
+        #[csv(field = "name")]
+        name: String,
+        #[csv(field = "age")]
+        age: u32,
+    }
+
+    #[test]
+    fn test_read_simple() {
+        block_on(async {
+            let data = "a,\"b\nc\"\r\nd,e\n";
+            let mut rd = Reader::new(data.as_bytes());
+            let records = rd.string_records().await.unwrap();
+            assert_eq!(records, vec![vec!["a", "b\nc"], vec!["d", "e"]]);
+        });
+    }
+
+    #[test]
+    fn test_records_stream() {
+        block_on(async {
+            let data = "a,b\nc,d\ne\n";
+            let mut rd = Reader::new(data.as_bytes());
+            let records: Vec<_> = rd.records().collect().await;
+            assert_eq!(records.len(), 3);
+            assert_eq!(
+                records[1].as_ref().unwrap(),
+                &StringRecord::from(vec!["c", "d"])
+            );
+            assert!(matches!(records[2], Err(Error::ErrFieldNum(3, _, 2, 1))));
+        });
+    }
+
+    #[test]
+    fn test_deserialize_stream() {
+        block_on(async {
+            let data = "name,age\nalice,30\nbob,4\n";
+            let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+            let rows: Vec<Row> = rd
+                .deserialize_stream()
+                .map(|row| row.unwrap())
+                .collect()
+                .await;
+            assert_eq!(rows[1], Row {
+                name: "bob".to_string(),
+                age: 4
+            });
+        });
+    }
+
+    #[test]
+    fn test_seek() {
+        block_on(async {
+            let data = "a,b\nc,d\n";
+            let mut rd = Reader::new(Cursor::new(data));
+            let mut record = ByteRecord::new();
+            rd.read_byte_record(&mut record).await.unwrap();
+            let pos = rd.position();
+            assert!(rd.read_byte_record(&mut record).await.unwrap());
+            assert!(!rd.read_byte_record(&mut record).await.unwrap());
+            rd.seek(pos).await.unwrap();
+            assert!(rd.read_byte_record(&mut record).await.unwrap());
+            assert_eq!(record, ByteRecord::from(vec!["c", "d"]));
+        });
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        block_on(async {
+            let rows = vec![Row {
+                name: "a \"b\"".to_string(),
+                age: 1,
+            }];
+            let mut out = Vec::new();
+            Writer::new(&mut out)
+                .with_dialect(Dialect::unix())
+                .unwrap()
+                .serialize(&rows)
+                .await
+                .unwrap();
+            assert_eq!(out, "name,age\n\"a \"\"b\"\"\",1\n".as_bytes());
+            let mut rd = Reader::new(out.as_slice())
+                .with_dialect(Dialect::unix())
+                .unwrap();
+            assert_eq!(rd.deserialize::<Row>().await.unwrap(), rows);
+        });
+    }
+}
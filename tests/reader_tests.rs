@@ -1,9 +1,15 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "async"))]
 mod reader_test {
 
-    use csv::err::ErrorKind;
-    use csv::reader::Reader;
+    use csv::dialect::Dialect;
+    use csv::err::{BadRecord, ErrorPolicy, Limit};
+    use csv::reader::{Reader, Trim};
+    use csv::record::{ByteRecord, Position, StringRecord};
+    use csv::{Error, Terminator};
     use encoding_rs::GBK;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    use tokio_stream::StreamExt;
 
     #[tokio::test]
     async fn test_simple() {
@@ -63,6 +69,42 @@ zzz,yyy,xxx
         assert_eq!(records, vec![vec!["a", "b", "c"]]);
     }
 
+    #[tokio::test]
+    async fn test_multi_byte_delimiter() {
+        let data = "a||b|||c\n\"d||\"||\"e\"\"\"||\n";
+        let mut rd = Reader::new(data.as_bytes()).with_delimiter(b"||").unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b", "|c"], vec!["d||", "e\"", ""]]);
+    }
+
+    #[tokio::test]
+    async fn test_utf8_delimiter() {
+        let data = "a¦b\t|\tc\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_delimiter("¦".as_bytes())
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b\t|\tc"]]);
+        let mut rd = Reader::new(data.as_bytes())
+            .with_delimiter(b"\t|\t")
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a¦b", "c"]]);
+    }
+
+    #[tokio::test]
+    async fn test_bad_delimiter() {
+        let rd = Reader::new("".as_bytes()).with_delimiter(b"");
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
+        let rd = Reader::new("".as_bytes()).with_delimiter(b"|\"");
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
+        let rd = Reader::new("".as_bytes())
+            .with_delimiter(b"|'")
+            .unwrap()
+            .with_quote(b'\'');
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidQuote));
+    }
+
     #[tokio::test]
     async fn test_multiline() {
         let data = r#""two
@@ -94,6 +136,36 @@ field""#;
         assert_eq!(records, vec![vec!["a", "b", "c"], vec!["d", "e", "f"]]);
     }
 
+    #[tokio::test]
+    async fn test_comment() {
+        let data = "#field1,field2\na,b\n# a,b\n\nc,\"#d\n#e\"\n#\n";
+        let mut rd = Reader::new(data.as_bytes()).with_comment(b'#').unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["c", "#d\n#e"]]);
+    }
+
+    #[tokio::test]
+    async fn test_comment_line_number() {
+        let data = "a,b\n#c\n\nd\n";
+        let mut rd = Reader::new(data.as_bytes()).with_comment(b'#').unwrap();
+        let records = rd.string_records().await;
+        assert!(matches!(
+            records.err().unwrap(),
+            Error::ErrFieldNum(4, _, 2, 1)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bad_comment() {
+        let rd = Reader::new("".as_bytes()).with_comment(b',');
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
+        let rd = Reader::new("".as_bytes())
+            .with_comment(b'#')
+            .unwrap()
+            .with_comma(b'#');
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
+    }
+
     #[tokio::test]
     async fn test_leading_space() {
         let data = "a,  b,    c";
@@ -102,6 +174,48 @@ field""#;
         assert_eq!(records, vec![vec!["a", "  b", "    c"]]);
     }
 
+    #[tokio::test]
+    async fn test_trim_fields() {
+        let data = " h1 , h2 ,h3\n a,  \" b \"  ,\tc\u{3000}\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_has_header(true)
+            .with_trim(Trim::Fields);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", " b ", "c"]]);
+        assert_eq!(rd.headers().await.unwrap().unwrap(), [" h1 ", " h2 ", "h3"]);
+    }
+
+    #[tokio::test]
+    async fn test_trim_headers() {
+        let data = " h1 , h2 \n a , b \n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_has_header(true)
+            .with_trim(Trim::Headers);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec![" a ", " b "]]);
+        assert_eq!(rd.headers().await.unwrap().unwrap(), ["h1", "h2"]);
+    }
+
+    #[tokio::test]
+    async fn test_trim_all_quoted() {
+        let data = "\" h1\" , h2 \n \" a \"  ,\" b\n \"\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_has_header(true)
+            .with_trim(Trim::All)
+            .with_trim_quoted(true);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"]]);
+        assert_eq!(rd.headers().await.unwrap().unwrap(), ["h1", "h2"]);
+    }
+
+    #[tokio::test]
+    async fn test_trim_bad_quote() {
+        let data = " \"a\" b,c\n";
+        let mut rd = Reader::new(data.as_bytes()).with_trim(Trim::All);
+        let records = rd.string_records().await;
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 4)));
+    }
+
     #[tokio::test]
     async fn test_lazy_quote() {
         let data = r#"a "word","1"2",a","b"#;
@@ -131,11 +245,8 @@ field""#;
         let data = r#"a""b,c"#;
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 2)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 2)));
     }
 
     #[tokio::test]
@@ -143,11 +254,8 @@ field""#;
         let data = r#"a "word","b""#;
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 3)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 3)));
     }
 
     #[tokio::test]
@@ -155,11 +263,8 @@ field""#;
         let data = r#""a word",b""#;
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 11)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 11)));
     }
 
     #[tokio::test]
@@ -167,11 +272,8 @@ field""#;
         let data = r#""a "word","b""#;
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 4)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 4)));
     }
 
     #[tokio::test]
@@ -179,11 +281,11 @@ field""#;
         let data = "a,b,c\nd,e";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrFieldNum(2, 3, 3, 2)
-        );
+        assert!(records.is_err());
+        assert!(matches!(
+            records.err().unwrap(),
+            Error::ErrFieldNum(2, 3, 3, 2)
+        ));
     }
 
     #[tokio::test]
@@ -191,11 +293,11 @@ field""#;
         let data = "a,b,c\nd,e\nf";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrFieldNum(2, 3, 3, 2)
-        );
+        assert!(records.is_err());
+        assert!(matches!(
+            records.err().unwrap(),
+            Error::ErrFieldNum(2, 3, 3, 2)
+        ));
     }
 
     #[tokio::test]
@@ -294,11 +396,8 @@ x,,,
         let data = "a,\"b\nc\"d,e";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(2, 2)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(2, 2)));
     }
 
     #[tokio::test]
@@ -306,11 +405,8 @@ x,,,
         let data = "a,b\n\"d\n\n,e";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(4, 3)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(4, 3)));
     }
 
     #[tokio::test]
@@ -318,7 +414,7 @@ x,,,
         let data = "A,\"Hello\r\nHi\",B\r\n";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await.unwrap();
-        assert_eq!(records, vec![vec!["A", "Hello\nHi", "B"]]);
+        assert_eq!(records, vec![vec!["A", "Hello\r\nHi", "B"]]);
     }
 
     #[tokio::test]
@@ -342,11 +438,8 @@ x,,,
         let data = "\"field\"\r\r";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 7)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 7)));
     }
 
     #[tokio::test]
@@ -424,11 +517,8 @@ x,,,
         let data = "\"foo\"bar\"\r\n";
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 5)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 5)));
     }
 
     #[tokio::test]
@@ -460,44 +550,106 @@ x,,,
         let data = r#"""""""""#;
         let mut rd = Reader::new(data.as_bytes());
         let records = rd.string_records().await;
-        assert_eq!(records.is_err(), true);
-        assert_eq!(
-            *records.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrQuote(1, 8)
-        );
+        assert!(records.is_err());
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 8)));
     }
 
     #[tokio::test]
     async fn test_bad_comma1() {
         let data = "";
         let rd = Reader::new(data.as_bytes()).with_comma(b'\n');
-        assert_eq!(rd.is_err(), true);
-        assert_eq!(
-            *rd.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrInvalidDelim
-        );
+        assert!(rd.is_err());
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
     }
 
     #[tokio::test]
     async fn test_bad_comma2() {
         let data = "";
         let rd = Reader::new(data.as_bytes()).with_comma(b'\r');
-        assert_eq!(rd.is_err(), true);
-        assert_eq!(
-            *rd.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrInvalidDelim
-        );
+        assert!(rd.is_err());
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
     }
 
     #[tokio::test]
     async fn test_bad_comma3() {
         let data = "";
         let rd = Reader::new(data.as_bytes()).with_comma(b'\"');
-        assert_eq!(rd.is_err(), true);
-        assert_eq!(
-            *rd.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrInvalidDelim
-        );
+        assert!(rd.is_err());
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
+    }
+
+    #[tokio::test]
+    async fn test_single_quote() {
+        let data = "'a,b',\"c\",'d''e'\n";
+        let mut rd = Reader::new(data.as_bytes()).with_quote(b'\'').unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a,b", "\"c\"", "d'e"]]);
+    }
+
+    #[tokio::test]
+    async fn test_escape() {
+        let data = "\"a\\\"b\",\"c\\\\d\",\"e\\\nf\",g\\\\h\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .with_double_quote(false);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a\"b", "c\\d", "e\nf", "g\\h"]]);
+    }
+
+    #[tokio::test]
+    async fn test_escape_unquoted() {
+        // MySQL `SELECT ... INTO OUTFILE` escapes without enclosing fields
+        let data = "1,a\\,b,c\\\"d\\\\\n2,e\\\nf,g\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .with_double_quote(false);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["1", "a,b", "c\"d\\"], vec![
+            "2", "e\nf", "g"
+        ]]);
+
+        // `\0` is NUL, `\N` (NULL) is kept for null sentinels
+        let data = "1,\\N,a\\0b,\"\\N\"\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_escape(Some(b'\\'))
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["1", "\\N", "a\0b", "\\N"]]);
+
+        let data = "a\\,b,c\"d\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_escape(Some(b'\\'))
+            .unwrap();
+        let records = rd.string_records().await;
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 7)));
+    }
+
+    #[tokio::test]
+    async fn test_no_double_quote() {
+        let data = "\"a\"\"b\"\n";
+        let mut rd = Reader::new(data.as_bytes()).with_double_quote(false);
+        let records = rd.string_records().await;
+        assert!(matches!(records.err().unwrap(), Error::ErrQuote(1, 3)));
+    }
+
+    #[tokio::test]
+    async fn test_bad_quote_escape() {
+        let rd = Reader::new("".as_bytes()).with_quote(b',');
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidQuote));
+        let rd = Reader::new("".as_bytes()).with_escape(Some(b'"'));
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidEscape));
+        let rd = Reader::new("".as_bytes())
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .with_comma(b'\\');
+        assert!(matches!(rd.err().unwrap(), Error::ErrInvalidDelim));
+        let rd = Reader::new("".as_bytes())
+            .with_quote(b'\'')
+            .unwrap()
+            .with_comma(b'"');
+        assert!(rd.is_ok());
     }
 
     #[tokio::test]
@@ -508,6 +660,25 @@ x,,,
         assert_eq!(records, vec![vec!["a", "b", "c"]]);
     }
 
+    #[tokio::test]
+    async fn test_has_header() {
+        let data = "h1,h2,h3\na,b,c\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        assert_eq!(rd.headers().await.unwrap().unwrap(), ["h1", "h2", "h3"]);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b", "c"]]);
+        assert_eq!(rd.headers().await.unwrap().unwrap(), ["h1", "h2", "h3"]);
+    }
+
+    #[tokio::test]
+    async fn test_no_header() {
+        let data = "h1,h2,h3\na,b,c\n";
+        let mut rd = Reader::new(data.as_bytes()).with_skip_header(true);
+        assert_eq!(rd.headers().await.unwrap(), None);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b", "c"]]);
+    }
+
     #[tokio::test]
     async fn test_utf8() {
         let data = "你好，,こんにちは💖";
@@ -532,7 +703,343 @@ x,,,
         let mut rd = Reader::new(&data[..]).with_encoding(GBK);
         let records = rd.string_records().await.unwrap();
         assert_eq!(records, vec![vec!["你\r好，", "こんにちは"], vec![
-            "世\n界", "再见\n"
+            "世\n界",
+            "再见\r\n"
+        ]]);
+    }
+
+    #[tokio::test]
+    async fn test_records_stream() {
+        let data = "h1,h2\na,b\n\nc,d";
+        let mut rd = Reader::new(data.as_bytes()).with_skip_header(true);
+        let mut records = rd.records();
+        let record = records.next().await.unwrap().unwrap();
+        assert_eq!(record, StringRecord::from(vec!["a", "b"]));
+        assert_eq!(record.position().unwrap().line(), 2);
+        assert_eq!(record.position().unwrap().byte(), 6);
+        assert_eq!(record.position().unwrap().record(), 1);
+        let record = records.next().await.unwrap().unwrap();
+        assert_eq!(record, StringRecord::from(vec!["c", "d"]));
+        assert_eq!(record.position().unwrap().line(), 4);
+        assert_eq!(record.position().unwrap().byte(), 11);
+        assert_eq!(record.position().unwrap().record(), 2);
+        assert!(records.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_into_stream() {
+        let data = "a,b\nc,d\n";
+        let records: Vec<_> = Reader::new(data.as_bytes())
+            .into_stream()
+            .map(|r| r.unwrap().iter().map(String::from).collect::<Vec<_>>())
+            .collect()
+            .await;
+        assert_eq!(records, vec![vec!["a", "b"], vec!["c", "d"]]);
+    }
+
+    #[tokio::test]
+    async fn test_records_stream_stop_at_error() {
+        let data = "a,b\nc\nd,e\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let mut records = rd.records();
+        assert_eq!(
+            records.next().await.unwrap().unwrap(),
+            StringRecord::from(vec!["a", "b"])
+        );
+        let err = records.next().await.unwrap().err().unwrap();
+        assert!(matches!(err, Error::ErrFieldNum(2, 1, 2, 1)));
+        assert!(records.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_byte_record() {
+        let data = "a,\"b\nb\",c\nd,,f\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let mut record = ByteRecord::new();
+        assert!(rd.read_byte_record(&mut record).await.unwrap());
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.get(1), Some(&b"b\nb"[..]));
+        assert_eq!(record.get(3), None);
+        assert_eq!(record.as_slice(), b"ab\nbc");
+        assert_eq!(record.field_position(1).unwrap().byte(), 2);
+        assert_eq!(record.field_position(2).unwrap().line(), 2);
+        assert_eq!(record.field_position(2).unwrap().col(), 4);
+        assert_eq!(record.field_position(2).unwrap().byte(), 8);
+        assert!(rd.read_byte_record(&mut record).await.unwrap());
+        assert_eq!(record.iter().collect::<Vec<_>>(), vec![
+            &b"d"[..],
+            &b""[..],
+            &b"f"[..]
+        ]);
+        assert_eq!(record.position().unwrap().line(), 3);
+        assert_eq!(record.position().unwrap().byte(), 10);
+        assert_eq!(record.position().unwrap().record(), 1);
+        assert_eq!(record.field_position(2).unwrap().byte(), 13);
+        assert!(!rd.read_byte_record(&mut record).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_read_string_record() {
+        let data = "h1,h2\n你好,こんにちは\n";
+        let (data, _, _) = GBK.encode(data);
+        let mut rd = Reader::new(&data[..])
+            .with_encoding(GBK)
+            .with_skip_header(true);
+        let mut record = StringRecord::new();
+        assert!(rd.read_string_record(&mut record).await.unwrap());
+        assert_eq!(record, StringRecord::from(vec!["你好", "こんにちは"]));
+        assert_eq!(record.get(0), Some("你好"));
+        assert_eq!(record.as_str(), "你好こんにちは");
+        assert!(!rd.read_string_record(&mut record).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_seek() {
+        let data = "h1,h2\na,b\r\n\"c\nc\",d\ne,f\n";
+        let mut rd = Reader::new(Cursor::new(data.as_bytes())).with_has_header(true);
+        let mut record = StringRecord::new();
+        assert!(rd.read_string_record(&mut record).await.unwrap());
+        let pos = rd.position();
+        assert_eq!(pos, Position::new(11, 3, 2));
+        let rest = rd.string_records().await.unwrap();
+        assert_eq!(rest, vec![vec!["c\nc", "d"], vec!["e", "f"]]);
+
+        rd.seek(pos.clone()).await.unwrap();
+        assert!(rd.read_string_record(&mut record).await.unwrap());
+        assert_eq!(record, StringRecord::from(vec!["c\nc", "d"]));
+        assert_eq!(record.position(), Some(&pos));
+        assert!(rd.read_string_record(&mut record).await.unwrap());
+        assert_eq!(record.position(), Some(&Position::new(19, 5, 3)));
+        assert_eq!(rd.headers().await.unwrap().unwrap(), ["h1", "h2"]);
+
+        rd.seek(Position::new(0, 1, 0)).await.unwrap();
+        let all = rd.string_records().await.unwrap();
+        assert_eq!(all, vec![vec!["a", "b"], vec!["c\nc", "d"], vec!["e", "f"]]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8() {
+        let data = b"a,b\nc,\xff\n";
+        let mut rd = Reader::new(&data[..]);
+        let err = rd.string_records().await.err().unwrap();
+        match &err {
+            Error::ErrUtf8 { pos, field, .. } => {
+                assert_eq!(*field, 1);
+                assert_eq!(pos.as_ref().unwrap().line(), 2);
+                assert_eq!(pos.as_ref().unwrap().record(), 1);
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_error_policy_collect() {
+        let data = b"a,b\nc\nd,\"e\n\"f,g\nh,\xff\ni,j\n";
+        let mut rd = Reader::new(&data[..]).with_error_policy(ErrorPolicy::Collect);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["i", "j"]]);
+        let bad = rd.take_bad_records();
+        assert_eq!(bad.len(), 3);
+        assert!(matches!(bad[0].error, Error::ErrFieldNum(2, _, 2, 1)));
+        assert_eq!(bad[0].raw, b"c\n");
+        assert_eq!(bad[0].position.line(), 2);
+        assert!(matches!(bad[1].error, Error::ErrQuote(4, 1)));
+        assert_eq!(bad[1].raw, b"d,\"e\n\"f,g\n");
+        assert_eq!(bad[1].position.byte(), 6);
+        assert!(matches!(bad[2].error, Error::ErrUtf8 { field: 1, .. }));
+        assert_eq!(bad[2].raw, b"h,\xff\n");
+        assert_eq!(bad[2].position.record(), 3);
+        assert!(rd.bad_records().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_error_policy_skip() {
+        let data = "a,b\nc\"d,e\nf,g\n";
+        let seen = Arc::new(Mutex::new(Vec::<BadRecord>::new()));
+        let sink = seen.clone();
+        let mut rd = Reader::new(data.as_bytes())
+            .with_error_policy(ErrorPolicy::Skip)
+            .with_on_bad_record(move |bad| sink.lock().unwrap().push(bad));
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["f", "g"]]);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].position.line(), 2);
+        assert!(matches!(seen[0].error, Error::ErrQuote(2, 2)));
+        assert!(rd.bad_records().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_terminator_any() {
+        let data = "a,b\x1ec,\"d\x1ee\"\x1ef,g\nh\x1e";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_terminator(Terminator::Any(0x1e))
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["c", "d\x1ee"], vec![
+            "f", "g\nh"
+        ]]);
+    }
+
+    #[tokio::test]
+    async fn test_terminator_any_keeps_cr() {
+        let data = "a,b\r\x1ec,d";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_terminator(Terminator::Any(0x1e))
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b\r"], vec!["c", "d"]]);
+    }
+
+    #[tokio::test]
+    async fn test_terminator_any_newline() {
+        // mainframe exports end records with `;\n`
+        let data = "a,b;\nc,\"d;\ne\";\nf,g;\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_terminator(Terminator::Any(b';'))
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["c", "d;\ne"], vec![
+            "f", "g"
         ]]);
+        // positions start after the skipped line break
+        let mut rd = Reader::new(data.as_bytes())
+            .with_terminator(Terminator::Any(b';'))
+            .unwrap();
+        let mut records = rd.records();
+        let record = records.next().await.unwrap().unwrap();
+        assert_eq!(record.position().unwrap().byte(), 0);
+        records.next().await.unwrap().unwrap();
+        let record = records.next().await.unwrap().unwrap();
+        assert_eq!(record.position().unwrap().byte(), 15);
+        assert!(records.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_terminator_invalid() {
+        let rd = Reader::new("".as_bytes()).with_terminator(Terminator::Any(b','));
+        assert!(matches!(rd.err(), Some(Error::ErrInvalidTerminator)));
+        let rd = Reader::new("".as_bytes())
+            .with_terminator(Terminator::Any(b';'))
+            .unwrap()
+            .with_comma(b';');
+        assert!(matches!(rd.err(), Some(Error::ErrInvalidDelim)));
+    }
+
+    #[tokio::test]
+    async fn test_dialect_excel_tab() {
+        let data = "h1\th2\r\n a\t\"b\tc\"\r\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_dialect(Dialect::excel_tab().with_trim(Trim::All))
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b\tc"]]);
+        assert_eq!(
+            rd.headers().await.unwrap(),
+            Some(&["h1".to_string(), "h2".to_string()][..])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dialect_comment() {
+        let data = "a;b\n#c;d\ne;f\n";
+        let dialect = Dialect::postgres_copy()
+            .with_delimiter(b";")
+            .with_comment(Some(b'#'));
+        let mut rd = Reader::new(data.as_bytes()).with_dialect(dialect).unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["e", "f"]]);
+    }
+
+    #[tokio::test]
+    async fn test_dialect_invalid() {
+        let dialect = Dialect::rfc4180().with_delimiter(b"\"");
+        let rd = Reader::new("".as_bytes()).with_dialect(dialect);
+        assert!(matches!(rd.err(), Some(Error::ErrInvalidDelim)));
+        // settings are checked together, the delimiter may take the old quote
+        let dialect = Dialect::rfc4180().with_delimiter(b"\"").with_quote(b'\'');
+        assert!(Reader::new("".as_bytes()).with_dialect(dialect).is_ok());
+        // same as `with_double_quote(false)`
+        let dialect = Dialect::rfc4180().with_double_quote(false);
+        assert!(Reader::new("".as_bytes()).with_dialect(dialect).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_max_record_bytes() {
+        let mut rd = Reader::new(tokio::io::repeat(b'x')).with_max_record_bytes(1024);
+        let err = rd.string_records().await.unwrap_err();
+        assert!(matches!(err, Error::ErrLimit {
+            limit: Limit::RecordBytes,
+            max: 1024,
+            ..
+        }));
+
+        // an unterminated quote may not swallow the rest of the input
+        let data = format!("a,b\nc,\"d\n{}", "e\n".repeat(100));
+        let mut rd = Reader::new(data.as_bytes()).with_max_record_bytes(16);
+        let mut record = ByteRecord::new();
+        assert!(rd.read_byte_record(&mut record).await.unwrap());
+        let err = rd.read_byte_record(&mut record).await.unwrap_err();
+        let Error::ErrLimit { pos, .. } = err else {
+            panic!("{err}");
+        };
+        assert_eq!((pos.byte(), pos.line(), pos.record()), (4, 2, 1));
+
+        let mut rd = Reader::new("ab\ncd\n".as_bytes()).with_max_record_bytes(3);
+        assert_eq!(rd.string_records().await.unwrap(), vec![vec!["ab"], vec![
+            "cd"
+        ]]);
+    }
+
+    #[tokio::test]
+    async fn test_max_fields() {
+        let data = "a,b\nc,d,e\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_allow_diff_field_num(true)
+            .with_max_fields(2);
+        let err = rd.string_records().await.unwrap_err();
+        let Error::ErrLimit { pos, limit, max } = err else {
+            panic!("{err}");
+        };
+        assert_eq!((limit, max), (Limit::Fields, 2));
+        assert_eq!((pos.line(), pos.col()), (2, 5));
+
+        // the line is read in full, the reader goes on with the next one
+        let data = "a,b\nc,d,e\nf,g\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_allow_diff_field_num(true)
+            .with_max_fields(2)
+            .with_error_policy(ErrorPolicy::Collect);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["f", "g"]]);
+        assert_eq!(rd.bad_records().len(), 1);
+        assert_eq!(rd.bad_records()[0].raw, b"c,d,e\n");
+        assert!(matches!(rd.bad_records()[0].error, Error::ErrLimit {
+            limit: Limit::Fields,
+            ..
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_max_field_bytes() {
+        let data = "a,bb\n\"ccc\ncc\",d\n";
+        let mut rd = Reader::new(data.as_bytes()).with_max_field_bytes(4);
+        let mut record = ByteRecord::new();
+        assert!(rd.read_byte_record(&mut record).await.unwrap());
+        let err = rd.read_byte_record(&mut record).await.unwrap_err();
+        let Error::ErrLimit { pos, limit, .. } = err else {
+            panic!("{err}");
+        };
+        assert_eq!(limit, Limit::FieldBytes);
+        assert_eq!((pos.line(), pos.col()), (2, 1));
+
+        let data = "aaaaa,b\nc,d\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_max_field_bytes(3)
+            .with_error_policy(ErrorPolicy::Collect);
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["c", "d"]]);
+        assert_eq!(rd.bad_records().len(), 1);
     }
 }
@@ -0,0 +1,199 @@
+#[cfg(all(test, feature = "serde"))]
+mod serde_test {
+
+    use csv::Error;
+    use csv::sync::{Reader, Writer};
+    use serde::{Deserialize, Deserializer, Serialize};
+    use std::str::FromStr;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Side {
+        Buy,
+        Sell,
+        #[serde(rename = "-")]
+        Unknown,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        #[serde(rename = "order_id")]
+        id: u64,
+        side: Side,
+        price: Option<f64>,
+        #[serde(default)]
+        note: String,
+    }
+
+    // flattened fields are given as strings
+    fn from_str<'de, D, T>(d: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        String::deserialize(d)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Meta {
+        source: String,
+        code: String,
+        #[serde(deserialize_with = "from_str")]
+        seq: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Tagged {
+        name: String,
+        #[serde(flatten)]
+        meta: Meta,
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let data = "side,extra,price,order_id\nbuy,x,1.5,1\nsell,y,,2\n-,z,3,3\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        let orders: Vec<Order> = rd.deserialize_serde().unwrap();
+        assert_eq!(orders, vec![
+            Order {
+                id: 1,
+                side: Side::Buy,
+                price: Some(1.5),
+                note: String::new(),
+            },
+            Order {
+                id: 2,
+                side: Side::Sell,
+                price: None,
+                note: String::new(),
+            },
+            Order {
+                id: 3,
+                side: Side::Unknown,
+                price: Some(3.0),
+                note: String::new(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_deserialize_no_header() {
+        let data = "a,1\nb,2\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let rows: Vec<(String, u8)> = rd.deserialize_serde().unwrap();
+        assert_eq!(rows, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_deserialize_error() {
+        let data = "order_id,side\nx,buy\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        let err = rd.deserialize_serde::<Order>().unwrap_err();
+        assert!(matches!(err, Error::ErrParse { ref field, ty: "u64", .. } if field == "order_id"));
+
+        let data = "order_id,price\n1,2\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        let err = rd.deserialize_serde::<Order>().unwrap_err();
+        assert!(matches!(err, Error::ErrMissField(ref field) if field == "side"));
+
+        let data = "order_id,side\n1,hold\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        let err = rd.deserialize_serde::<Order>().unwrap_err();
+        assert!(matches!(err, Error::ErrSerde(_)));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut wr = Writer::new(Vec::new()).with_write_header(true);
+        wr.serialize_serde(&Order {
+            id: 1,
+            side: Side::Buy,
+            price: None,
+            note: "a, b".to_string(),
+        })
+        .unwrap();
+        wr.serialize_serde(&Order {
+            id: 2,
+            side: Side::Unknown,
+            price: Some(0.5),
+            note: String::new(),
+        })
+        .unwrap();
+        let out = wr.finish().unwrap();
+        assert_eq!(
+            out,
+            "order_id,side,price,note\n1,buy,,\"a, b\"\n2,-,0.5,\n".as_bytes()
+        );
+        let mut rd = Reader::new(out.as_slice()).with_has_header(true);
+        let orders: Vec<Order> = rd.deserialize_serde().unwrap();
+        assert_eq!(orders[1].side, Side::Unknown);
+        assert_eq!(orders[1].price, Some(0.5));
+    }
+
+    #[test]
+    fn test_serialize_custom_header() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Row {
+            a: u32,
+            b: String,
+        }
+        let row = Row {
+            a: 1,
+            b: "x".to_string(),
+        };
+        let mut wr = Writer::new(Vec::new())
+            .with_write_header(true)
+            .with_custom_header(vec!["b".to_string(), "a".to_string()]);
+        wr.serialize_serde(&row).unwrap();
+        let out = wr.finish().unwrap();
+        assert_eq!(out, "b,a\nx,1\n".as_bytes());
+        let mut rd = Reader::new(out.as_slice()).with_has_header(true);
+        assert_eq!(rd.deserialize_serde::<Row>().unwrap(), vec![row]);
+
+        let mut wr = Writer::new(Vec::new())
+            .with_write_header(true)
+            .with_custom_header(vec!["a".to_string(), "c".to_string()]);
+        let err = wr.serialize_serde(&Row {
+            a: 1,
+            b: "x".to_string(),
+        });
+        assert!(matches!(err, Err(Error::ErrMissField(ref field)) if field == "c"));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let row = Tagged {
+            name: "a".to_string(),
+            meta: Meta {
+                source: "true".to_string(),
+                code: "007".to_string(),
+                seq: 7,
+            },
+        };
+        let mut wr = Writer::new(Vec::new()).with_write_header(true);
+        wr.serialize_serde(&row).unwrap();
+        let out = wr.finish().unwrap();
+        assert_eq!(out, "name,source,code,seq\na,true,007,7\n".as_bytes());
+        let mut rd = Reader::new(out.as_slice()).with_has_header(true);
+        assert_eq!(rd.deserialize_serde::<Tagged>().unwrap(), vec![row]);
+
+        // without `deserialize_with` a flattened number is a clear error
+        #[derive(Deserialize, Debug)]
+        struct Inner {
+            #[allow(dead_code)]
+            seq: Option<f64>,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Outer {
+            #[allow(dead_code)]
+            #[serde(flatten)]
+            inner: Inner,
+        }
+        let mut rd = Reader::new("seq\n7\n".as_bytes()).with_has_header(true);
+        let err = rd.deserialize_serde::<Outer>().unwrap_err();
+        assert!(matches!(err, Error::ErrSerde(ref msg) if msg.contains("flattened struct")));
+    }
+}
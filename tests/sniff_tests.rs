@@ -0,0 +1,80 @@
+#[cfg(all(test, feature = "async"))]
+mod sniff_test {
+
+    use csv::reader::Reader;
+    use csv::{Terminator, sniff};
+    use encoding_rs::GBK;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_semicolon_header() {
+        let data = "name;age\r\nalice;30\r\nbob;4\r\n";
+        let sniffed = sniff(&mut data.as_bytes(), 1024).await.unwrap();
+        assert_eq!(sniffed.dialect.delimiter, b";");
+        assert_eq!(sniffed.dialect.quote, b'"');
+        assert_eq!(sniffed.dialect.terminator, Terminator::CRLF);
+        assert!(sniffed.dialect.header);
+        assert_eq!(sniffed.dialect.encoding, None);
+        assert_eq!(sniffed.columns, 2);
+        assert_eq!(sniffed.confidence, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_tab_no_header() {
+        let data = "1\ta,b\t2.5\n2\tc\t3\n3\td;e\t4\n";
+        let sniffed = sniff(&mut data.as_bytes(), 1024).await.unwrap();
+        assert_eq!(sniffed.dialect.delimiter, b"\t");
+        assert_eq!(sniffed.dialect.terminator, Terminator::Any(b'\n'));
+        assert!(!sniffed.dialect.header);
+        assert_eq!(sniffed.columns, 3);
+    }
+
+    #[tokio::test]
+    async fn test_single_quote() {
+        let data = "id|note\n1|'a|b'\n2|'it''s'\n";
+        let sniffed = sniff(&mut data.as_bytes(), 1024).await.unwrap();
+        assert_eq!(sniffed.dialect.delimiter, b"|");
+        assert_eq!(sniffed.dialect.quote, b'\'');
+        assert!(sniffed.dialect.header);
+    }
+
+    #[tokio::test]
+    async fn test_record_separator() {
+        let data = "a,b\x1ec,d\x1e";
+        let sniffed = sniff(&mut data.as_bytes(), 1024).await.unwrap();
+        assert_eq!(sniffed.dialect.terminator, Terminator::Any(0x1e));
+        assert_eq!(sniffed.columns, 2);
+    }
+
+    #[tokio::test]
+    async fn test_gbk() {
+        let (data, _, _) = GBK.encode("名字,年龄\n张小三,30\n李小四,40\n");
+        let sniffed = sniff(&mut data.as_ref(), 1024).await.unwrap();
+        assert_eq!(sniffed.dialect.encoding, Some(GBK));
+        assert_eq!(sniffed.columns, 2);
+        assert!(sniffed.dialect.header);
+    }
+
+    #[tokio::test]
+    async fn test_single_column() {
+        let data = "a\nb\n";
+        let sniffed = sniff(&mut data.as_bytes(), 1024).await.unwrap();
+        assert_eq!(sniffed.columns, 1);
+        assert!(sniffed.confidence <= 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_sample_then_reader() {
+        let data = "h1|h2\n1|2\n3|4\n5|6\n";
+        let mut input = data.as_bytes();
+        let sniffed = sniff(&mut input, 12).await.unwrap();
+        assert_eq!(sniffed.sample, b"h1|h2\n1|2\n3|");
+        let mut rd = Reader::new(sniffed.sample.as_slice().chain(input))
+            .with_dialect(sniffed.dialect)
+            .unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["1", "2"], vec!["3", "4"], vec![
+            "5", "6"
+        ]]);
+    }
+}
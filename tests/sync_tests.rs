@@ -0,0 +1,191 @@
+#[cfg(test)]
+mod sync_test {
+
+    use csv::dialect::Dialect;
+    use csv::err::{ErrorPolicy, Limit};
+    use csv::record::{ByteRecord, StringRecord};
+    use csv::sync::{Reader, Writer};
+    use csv::{Error, Terminator};
+    use macros::{CSVFrom, CSVHeader, CSVTo};
+    use std::io::Cursor;
+
+    #[derive(CSVHeader, CSVFrom, CSVTo, Debug, Default, PartialEq)]
+    struct Row {
+        #[csv(field = "name")]
+        name: String,
+        #[csv(field = "age")]
+        age: u32,
+    }
+
+    #[test]
+    fn test_read_simple() {
+        let data = "a,b,c\r\nd,e,f\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let records = rd.string_records().unwrap();
+        assert_eq!(records, vec![vec!["a", "b", "c"], vec!["d", "e", "f"]]);
+    }
+
+    #[test]
+    fn test_read_multiline_quoted() {
+        let data = "a,\"b\r\nc\"\"d\"\n# skipped\ne,f";
+        let mut rd = Reader::new(data.as_bytes()).with_comment(b'#').unwrap();
+        let records = rd.string_records().unwrap();
+        assert_eq!(records, vec![vec!["a", "b\r\nc\"d"], vec!["e", "f"]]);
+    }
+
+    #[test]
+    fn test_read_error() {
+        let data = "a,b\nc\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let err = rd.string_records().unwrap_err();
+        assert!(matches!(err, Error::ErrFieldNum(2, _, 2, 1)));
+    }
+
+    #[test]
+    fn test_read_error_policy() {
+        let data = "a,b\nc\"d,e\nf,g\n";
+        let mut rd = Reader::new(data.as_bytes()).with_error_policy(ErrorPolicy::Collect);
+        let records = rd.string_records().unwrap();
+        assert_eq!(records, vec![vec!["a", "b"], vec!["f", "g"]]);
+        assert_eq!(rd.bad_records()[0].raw, b"c\"d,e\n");
+    }
+
+    #[test]
+    fn test_read_bad_header() {
+        let data = "h\"x\n1\n";
+        let mut rd = Reader::new(data.as_bytes())
+            .with_has_header(true)
+            .with_error_policy(ErrorPolicy::Collect);
+        assert_eq!(rd.headers().unwrap(), None);
+        assert_eq!(rd.string_records().unwrap(), vec![vec!["1"]]);
+        assert_eq!(rd.bad_records().len(), 1);
+        assert_eq!(rd.bad_records()[0].raw, b"h\"x\n");
+
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        assert!(matches!(rd.headers(), Err(Error::ErrQuote(1, 2))));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let data = "age,name\n30,alice\n4,bob\n";
+        let mut rd = Reader::new(data.as_bytes()).with_has_header(true);
+        let rows: Vec<Row> = rd.deserialize().unwrap();
+        assert_eq!(rows[0], Row {
+            name: "alice".to_string(),
+            age: 30
+        });
+        assert_eq!(rows[1].age, 4);
+        assert_eq!(
+            rd.headers().unwrap(),
+            Some(&["age".to_string(), "name".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn test_records_iter() {
+        let data = "a,b\nc,d\ne\nf,g\n";
+        let mut rd = Reader::new(data.as_bytes());
+        let records: Vec<_> = rd.records().collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[1].as_ref().unwrap(),
+            &StringRecord::from(vec!["c", "d"])
+        );
+        assert!(matches!(records[2], Err(Error::ErrFieldNum(3, _, 2, 1))));
+    }
+
+    #[test]
+    fn test_seek() {
+        let data = "a,b\nc,d\ne,f\n";
+        let mut rd = Reader::new(Cursor::new(data));
+        let mut record = ByteRecord::new();
+        rd.read_byte_record(&mut record).unwrap();
+        let pos = rd.position();
+        rd.read_byte_record(&mut record).unwrap();
+        assert_eq!(record, ByteRecord::from(vec!["c", "d"]));
+        rd.seek(pos).unwrap();
+        assert!(rd.read_byte_record(&mut record).unwrap());
+        assert_eq!(record, ByteRecord::from(vec!["c", "d"]));
+        assert_eq!(record.position().unwrap().line(), 2);
+    }
+
+    #[test]
+    fn test_max_record_bytes() {
+        let mut rd = Reader::new(std::io::repeat(b'x')).with_max_record_bytes(64);
+        let err = rd.string_records().unwrap_err();
+        assert!(matches!(err, Error::ErrLimit {
+            limit: Limit::RecordBytes,
+            ..
+        }));
+    }
+
+    #[test]
+    fn test_write_simple() {
+        let data = vec![vec!["a", "b,c"], vec!["d\"e", "f\ng"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out).write_records(data).unwrap();
+        assert_eq!(out, "a,\"b,c\"\n\"d\"\"e\",\"f\ng\"\n".as_bytes());
+    }
+
+    #[test]
+    fn test_write_drop() {
+        let mut out = Vec::new();
+        let mut wr = Writer::new(&mut out);
+        wr.write_record(["a", "b"]).unwrap();
+        wr.write_record(["c", "d"]).unwrap();
+        drop(wr);
+        assert_eq!(out, "a,b\nc,d\n".as_bytes());
+    }
+
+    #[test]
+    fn test_write_terminator() {
+        let data = vec![vec!["a", "b"], vec!["c", "d"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_terminator(Terminator::Any(0x1e))
+            .unwrap()
+            .write_records(data)
+            .unwrap();
+        assert_eq!(out, "a,b\x1ec,d\x1e".as_bytes());
+    }
+
+    #[test]
+    fn test_serialize_one() {
+        let mut wr = Writer::new(Vec::new()).with_write_header(true);
+        for age in 0..3 {
+            let row = Row {
+                name: format!("n{age}"),
+                age,
+            };
+            wr.serialize_one(&row).unwrap();
+        }
+        wr.serialize(&[Row::default()]).unwrap();
+        let out = wr.finish().unwrap();
+        assert_eq!(out, "name,age\nn0,0\nn1,1\nn2,2\n,0\n".as_bytes());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let rows = vec![
+            Row {
+                name: "alice, jr".to_string(),
+                age: 30,
+            },
+            Row {
+                name: "bob".to_string(),
+                age: 4,
+            },
+        ];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_dialect(Dialect::rfc4180())
+            .unwrap()
+            .serialize(&rows)
+            .unwrap();
+        assert_eq!(out, "name,age\r\n\"alice, jr\",30\r\nbob,4\r\n".as_bytes());
+        let mut rd = Reader::new(out.as_slice())
+            .with_dialect(Dialect::rfc4180())
+            .unwrap();
+        assert_eq!(rd.deserialize::<Row>().unwrap(), rows);
+    }
+}
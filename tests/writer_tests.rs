@@ -1,8 +1,10 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "async"))]
 mod writer_test {
 
-    use csv::err::ErrorKind;
-    use csv::writer::Writer;
+    use csv::dialect::Dialect;
+    use csv::reader::Reader;
+    use csv::writer::{QuoteStyle, Writer};
+    use csv::{Error, Terminator};
     use encoding_rs::GBK;
     use std::str::from_utf8;
 
@@ -99,7 +101,7 @@ mod writer_test {
             .write_records(data)
             .await
             .unwrap();
-        assert_eq!(out, "\"abc\r\ndef\"\r\n".as_bytes());
+        assert_eq!(out, "\"abc\ndef\"\r\n".as_bytes());
     }
 
     #[tokio::test]
@@ -111,7 +113,7 @@ mod writer_test {
             .write_records(data)
             .await
             .unwrap();
-        assert_eq!(out, "\"abcdef\"\r\n".as_bytes());
+        assert_eq!(out, "\"abc\rdef\"\r\n".as_bytes());
     }
 
     #[tokio::test]
@@ -127,7 +129,7 @@ mod writer_test {
         let data = vec![vec![""]];
         let mut out = Vec::new();
         Writer::new(&mut out).write_records(data).await.unwrap();
-        assert_eq!(out, "\n".as_bytes());
+        assert_eq!(out, "\"\"\n".as_bytes());
     }
 
     #[tokio::test]
@@ -228,15 +230,101 @@ mod writer_test {
         assert_eq!(out, ",|,|\n".as_bytes());
     }
 
+    #[tokio::test]
+    async fn test_multi_byte_delimiter() {
+        let data = vec![vec!["a", "b||c", "d|", "|e", ""]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_delimiter(b"||")
+            .unwrap()
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(out, "a||\"b||c\"||\"d|\"|||e||\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_utf8_delimiter() {
+        let data = vec![vec!["a", "b¦", "c"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_delimiter("¦".as_bytes())
+            .unwrap()
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(from_utf8(&out).unwrap(), "a¦\"b¦\"¦c\n");
+    }
+
     #[tokio::test]
     async fn test_invalid_comma() {
         let mut out = Vec::new();
         let wt = Writer::new(&mut out).with_comma(b'"');
-        assert_eq!(wt.is_err(), true);
-        assert_eq!(
-            *wt.err().unwrap().downcast_ref::<ErrorKind>().unwrap(),
-            ErrorKind::ErrInvalidDelim
-        );
+        assert!(wt.is_err());
+        assert!(matches!(wt.err().unwrap(), Error::ErrInvalidDelim));
+    }
+
+    #[tokio::test]
+    async fn test_single_quote() {
+        let data = vec![vec!["a,b", "\"c\"", "d'e"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_quote(b'\'')
+            .unwrap()
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(out, "'a,b',\"c\",'d''e'\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_escape() {
+        let data = vec![vec!["a\"b", "c\\d", "e\\f,"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .with_double_quote(false)
+            .write_records(data.clone())
+            .await
+            .unwrap();
+        assert_eq!(out, "\"a\\\"b\",\"c\\\\d\",\"e\\\\f,\"\n".as_bytes());
+        let mut rd = Reader::new(out.as_slice())
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .with_double_quote(false);
+        assert_eq!(rd.string_records().await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_quote() {
+        let mut out = Vec::new();
+        let wt = Writer::new(&mut out).with_quote(b',');
+        assert!(matches!(wt.err().unwrap(), Error::ErrInvalidQuote));
+        let mut out = Vec::new();
+        let wt = Writer::new(&mut out).with_escape(Some(b'"'));
+        assert!(matches!(wt.err().unwrap(), Error::ErrInvalidEscape));
+    }
+
+    #[tokio::test]
+    async fn test_no_double_quote() {
+        // a quote could be neither doubled nor escaped, checked once writing
+        let mut wt = Writer::new(Vec::new()).with_double_quote(false);
+        let err = wt.write_record(["a"]).await.unwrap_err();
+        assert!(matches!(err, Error::ErrInvalidEscape));
+        let mut wt = Writer::new(Vec::new())
+            .with_dialect(Dialect::unix().with_double_quote(false))
+            .unwrap();
+        let err = wt.write_records([["a"]]).await.unwrap_err();
+        assert!(matches!(err, Error::ErrInvalidEscape));
+
+        // the builders may come in any order
+        let mut wt = Writer::new(Vec::new())
+            .with_double_quote(false)
+            .with_escape(Some(b'\\'))
+            .unwrap();
+        wt.write_record(["a\"b"]).await.unwrap();
+        assert_eq!(wt.finish().await.unwrap(), "\"a\\\"b\"\n".as_bytes());
     }
 
     #[tokio::test]
@@ -276,4 +364,277 @@ mod writer_test {
         let (expect, _, _) = GBK.encode("你好，,こんにちは,\n");
         assert_eq!(out, expect.to_vec());
     }
+
+    #[tokio::test]
+    async fn test_terminator_any() {
+        let data = vec![vec!["a", "b\x1ec"], vec!["d\ne", "f"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_terminator(Terminator::Any(0x1e))
+            .unwrap()
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(out, "a,\"b\x1ec\"\x1e\"d\ne\",f\x1e".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_terminator_crlf() {
+        let data = vec![vec!["a", "b\nc"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_terminator(Terminator::CRLF)
+            .unwrap()
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(out, "a,\"b\nc\"\r\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_terminator_invalid() {
+        let mut out = Vec::new();
+        let w = Writer::new(&mut out).with_terminator(Terminator::Any(b'"'));
+        assert!(matches!(w.err(), Some(Error::ErrInvalidTerminator)));
+    }
+
+    #[tokio::test]
+    async fn test_dialect_unix() {
+        let data = vec![vec!["a", "b\nc"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_dialect(Dialect::unix())
+            .unwrap()
+            .with_custom_header(vec!["h1".to_string(), "h2".to_string()])
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(out, "h1,h2\na,\"b\nc\"\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_dialect_round_trip() {
+        let dialect = Dialect::excel_tab().with_comment(Some(b'#'));
+        let data = vec![vec!["#a", "b\tc"], vec!["d", "e\r\nf"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_dialect(dialect.clone())
+            .unwrap()
+            .with_custom_header(vec!["h1".to_string(), "h2".to_string()])
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(
+            out,
+            "h1\th2\r\n\"#a\"\t\"b\tc\"\r\nd\t\"e\r\nf\"\r\n".as_bytes()
+        );
+        let mut rd = Reader::new(out.as_slice()).with_dialect(dialect).unwrap();
+        let records = rd.string_records().await.unwrap();
+        assert_eq!(records, vec![vec!["#a", "b\tc"], vec!["d", "e\r\nf"]]);
+    }
+
+    #[tokio::test]
+    async fn test_header_once() {
+        let mut out = Vec::new();
+        let mut wr = Writer::new(&mut out)
+            .with_custom_header(vec!["h1".to_string(), "h2".to_string()])
+            .with_write_header(true);
+        wr.write_records(vec![vec!["a", "b"]]).await.unwrap();
+        wr.write_records([["c", "d"]]).await.unwrap();
+        assert_eq!(out, "h1,h2\na,b\nc,d\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_write_record() {
+        let mut wr = Writer::new(Vec::new())
+            .with_custom_header(vec!["h1".to_string(), "h2".to_string()])
+            .with_write_header(true)
+            .with_encoding(GBK);
+        wr.write_record(["a", "b,c"]).await.unwrap();
+        wr.write_record([&b"\xff"[..], "张三".as_bytes()])
+            .await
+            .unwrap();
+        wr.flush().await.unwrap();
+        wr.write_record(vec![String::from("d"), String::new()])
+            .await
+            .unwrap();
+        let out = wr.finish().await.unwrap();
+        let mut expect = b"h1,h2\na,\"b,c\"\n\xff,".to_vec();
+        expect.extend_from_slice(&GBK.encode("张三").0);
+        expect.extend_from_slice(b"\nd,\n");
+        assert_eq!(out, expect);
+    }
+
+    #[tokio::test]
+    async fn test_quote_always() {
+        let data = vec![vec!["a", "", "1"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_quote_style(QuoteStyle::Always)
+            .with_custom_header(vec!["h1".to_string(), "h2".to_string(), "h3".to_string()])
+            .with_write_header(true)
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(out, "\"h1\",\"h2\",\"h3\"\n\"a\",\"\",\"1\"\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_quote_non_numeric() {
+        let data = vec![vec![
+            "a", "1", "-2.5", "1e-3", ".5", "", "1e", "inf", "b\"c",
+        ]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_quote_style(QuoteStyle::NonNumeric)
+            .write_records(data)
+            .await
+            .unwrap();
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "\"a\",1,-2.5,1e-3,.5,\"\",\"1e\",\"inf\",\"b\"\"c\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quote_never() {
+        let data = vec![vec!["a,b", "c\"d", "e\\f\ng"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_quote_style(QuoteStyle::Never)
+            .write_records(data.clone())
+            .await
+            .unwrap();
+        assert_eq!(out, "a,b,c\"d,e\\f\ng\n".as_bytes());
+
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_quote_style(QuoteStyle::Never)
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .write_records(data.clone())
+            .await
+            .unwrap();
+        assert_eq!(out, "a\\,b,c\\\"d,e\\\\f\\\ng\n".as_bytes());
+        let mut rd = Reader::new(out.as_slice())
+            .with_escape(Some(b'\\'))
+            .unwrap();
+        assert_eq!(rd.string_records().await.unwrap(), data);
+
+        let data = vec![vec!["a\r\n", "\"b\r"], vec!["c\n", "d"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out)
+            .with_quote_style(QuoteStyle::Never)
+            .with_use_crlf(true)
+            .with_escape(Some(b'\\'))
+            .unwrap()
+            .write_records(data.clone())
+            .await
+            .unwrap();
+        let mut rd = Reader::new(out.as_slice())
+            .with_escape(Some(b'\\'))
+            .unwrap();
+        assert_eq!(rd.string_records().await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_long_quoted_field() {
+        let field = "{\"k\": \"v\"}\n".repeat(100);
+        let data = vec![vec![field.as_str(), "x"]];
+        let mut out = Vec::new();
+        Writer::new(&mut out).write_records(data).await.unwrap();
+        let expect = format!("\"{}\",x\n", field.replace('"', "\"\""));
+        assert_eq!(from_utf8(&out).unwrap(), expect);
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let unix = Dialect::unix().with_header(false);
+        let dialects = [
+            (unix.clone(), &b"a \",\n\r#"[..]),
+            (unix.clone().with_escape(Some(b'\\')), b"a \",\n\r\\"),
+            (
+                unix.clone()
+                    .with_escape(Some(b'\\'))
+                    .with_double_quote(false),
+                b"a \",\n\r\\",
+            ),
+            (
+                unix.clone().with_delimiter(b"||").with_comment(Some(b'#')),
+                b"a |\"\n#",
+            ),
+            (
+                unix.with_terminator(Terminator::Any(0x1e)),
+                b"a ,\"\n\r\x1e",
+            ),
+            (Dialect::rfc4180().with_header(false), b"a ,\"\n\r"),
+        ];
+        let mut seed = 42u64;
+        let mut rand = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) as usize % n
+        };
+        let mut gen_records = |alphabet: &[u8], fields: usize| -> Vec<Vec<Vec<u8>>> {
+            (0..300)
+                .map(|_| {
+                    (0..fields)
+                        .map(|_| {
+                            (0..rand(16))
+                                .map(|_| alphabet[rand(alphabet.len())])
+                                .collect()
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        // single field records are often empty
+        for ((dialect, alphabet), fields) in dialects.iter().flat_map(|d| [(d, 1), (d, 3)]) {
+            let records = gen_records(alphabet, fields);
+            let mut wr = Writer::new(Vec::new())
+                .with_dialect(dialect.clone())
+                .unwrap();
+            for record in &records {
+                wr.write_record(record).await.unwrap();
+            }
+            let out = wr.finish().await.unwrap();
+            let mut rd = Reader::new(out.as_slice())
+                .with_dialect(dialect.clone())
+                .unwrap();
+            assert_eq!(rd.bytes_records().await.unwrap(), records);
+        }
+
+        // `\n` terminated output read back with the default `\r\n` handling
+        for fields in [1, 3] {
+            let records = gen_records(b"a ,\"\n\r", fields);
+            let mut wr = Writer::new(Vec::new());
+            for record in &records {
+                wr.write_record(record).await.unwrap();
+            }
+            let out = wr.finish().await.unwrap();
+            let mut rd = Reader::new(out.as_slice());
+            assert_eq!(rd.bytes_records().await.unwrap(), records);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_record() {
+        let data = vec![vec![""], vec!["a"]];
+        for style in [QuoteStyle::Necessary, QuoteStyle::Never] {
+            let mut out = Vec::new();
+            Writer::new(&mut out)
+                .with_quote_style(style)
+                .write_records(data.clone())
+                .await
+                .unwrap();
+            assert_eq!(out, "\"\"\na\n".as_bytes());
+            let mut rd = Reader::new(out.as_slice());
+            assert_eq!(rd.string_records().await.unwrap(), data);
+        }
+
+        let mut wr = Writer::new(Vec::new());
+        let err = wr.write_record(Vec::<&str>::new()).await.unwrap_err();
+        assert!(matches!(err, Error::ErrEmptyRecord));
+        wr.write_record(["a"]).await.unwrap();
+        assert_eq!(wr.finish().await.unwrap(), "a\n".as_bytes());
+    }
 }